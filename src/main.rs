@@ -14,8 +14,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
 
     dotenv().expect(".env should be present");
 
-
     service::telegram_service::main();
 
     Ok(())
-}
\ No newline at end of file
+}
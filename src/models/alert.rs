@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AlertCondition {
+    Above(f64),
+    Below(f64),
+    // Absolute move of at least `percent` within the last `window_minutes`
+    PercentMove { percent: f64, window_minutes: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: u64,
+    pub chat_id: i64,
+    pub symbol: String,
+    pub condition: AlertCondition,
+    pub created_at: i64,
+    // Cleared when the alert fires and set again once the condition is no longer met
+    pub armed: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AlertBook {
+    pub next_id: u64,
+    pub alerts: Vec<Alert>,
+}
+
+impl fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertCondition::Above(price) => write!(f, "above {price}"),
+            AlertCondition::Below(price) => write!(f, "below {price}"),
+            AlertCondition::PercentMove {
+                percent,
+                window_minutes,
+            } => {
+                write!(f, "moves {percent}% in {}", format_window(*window_minutes))
+            }
+        }
+    }
+}
+
+pub fn format_window(minutes: u32) -> String {
+    if minutes.is_multiple_of(1440) {
+        format!("{}d", minutes / 1440)
+    } else if minutes.is_multiple_of(60) {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
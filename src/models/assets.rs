@@ -1,4 +1,7 @@
-use teloxide::{types::{ChatId, Recipient}, Bot};
+use teloxide::{
+    types::{ChatId, Recipient},
+    Bot,
+};
 use thiserror::Error;
 
 // For send info to service chat
@@ -40,4 +43,176 @@ pub enum LogError {
 pub enum MessageError {
     #[error("No data found for message: {0}")]
     NoDataFound(String),
-}
\ No newline at end of file
+}
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Failed to access the store: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to serialize data: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("Binance request failed: {0}")]
+    Binance(String),
+    #[error("Binance task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("Invalid trading pair: {0}")]
+    InvalidSymbol(String),
+    #[error("Unsupported interval: {0}")]
+    InvalidInterval(String),
+    #[error("Not enough price history for {0}")]
+    NotEnoughHistory(String),
+    #[error("Failed to render chart: {0}")]
+    Chart(#[from] ChartError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Error, Debug)]
+pub enum ChartError {
+    #[error("No data to draw")]
+    NoData,
+    #[error("Failed to draw chart: {0}")]
+    Draw(String),
+    #[error("Failed to encode chart: {0}")]
+    Encode(#[from] image::ImageError),
+}
+
+#[derive(Error, Debug)]
+pub enum AlertError {
+    #[error("Could not understand the alert: {0}")]
+    InvalidFormat(String),
+    #[error("No alert with id {0} in this chat")]
+    NotFound(u64),
+    #[error("A chat can have at most {0} alerts")]
+    TooMany(usize),
+    #[error("Alert windows can be at most {0} hours long")]
+    WindowTooLong(u32),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Error, Debug)]
+pub enum PortfolioError {
+    #[error("Could not understand the trade: {0}")]
+    InvalidTrade(String),
+    #[error("Cannot sell {requested} {symbol} on {date}, only {held} held then")]
+    InsufficientHoldings {
+        symbol: String,
+        requested: String,
+        held: String,
+        date: String,
+    },
+    #[error("The trade is too large to track: {0}")]
+    TooLarge(String),
+    #[error("Please add the price of the trade on {0}, the live price only covers today")]
+    MissingPrice(String),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Error, Debug)]
+pub enum ConversionError {
+    #[error("Could not understand the conversion: {0}")]
+    InvalidFormat(String),
+    #[error("No Binance market links {0} and {1}")]
+    NoRoute(String, String),
+    #[error("The converted amount is too large: {0}")]
+    TooLarge(String),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+}
+
+#[derive(Error, Debug)]
+pub enum PaperError {
+    #[error("Could not understand the order: {0}")]
+    InvalidOrder(String),
+    #[error("Paper trading only supports USDT pairs, got {0}")]
+    UnsupportedPair(String),
+    #[error("Insufficient balance: need {needed} USDT, have {available} USDT")]
+    InsufficientBalance { needed: String, available: String },
+    #[error("Insufficient holdings: need {needed} {symbol}, have {available}")]
+    InsufficientHoldings {
+        symbol: String,
+        needed: String,
+        available: String,
+    },
+    #[error("No open order with id {0}")]
+    OrderNotFound(u64),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Error, Debug)]
+pub enum BacktestError {
+    #[error("Could not understand the backtest: {0}")]
+    InvalidFormat(String),
+    #[error("The start date {0} must be in the past")]
+    InvalidStart(String),
+    #[error("No daily price history for {0} since the start date")]
+    NoHistory(String),
+    #[error("Backtest task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    Chart(#[from] ChartError),
+}
+
+#[derive(Error, Debug)]
+pub enum AiError {
+    #[error("LLM_API_KEY is not set")]
+    MissingKey,
+    #[error("The AI request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("The AI service answered {status}: {message}")]
+    Api { status: u16, message: String },
+    // Already worded for the user
+    #[error("{0}")]
+    QuotaExceeded(String),
+    // Refused by the moderation layer, also worded for the user
+    #[error("{0}")]
+    Moderated(String),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Error, Debug)]
+pub enum SpeechError {
+    #[error("Voice messages up to {limit} seconds are supported, this one is {seconds}")]
+    TooLong { seconds: u32, limit: u32 },
+    #[error("Voice messages up to {limit} MB are supported")]
+    TooLarge { limit: u32 },
+    #[error("Failed to fetch the voice message: {0}")]
+    Telegram(#[from] teloxide::RequestError),
+    #[error("Failed to download the voice message: {0}")]
+    Download(#[from] teloxide::DownloadError),
+    #[error("Failed to build the speech request: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Ai(#[from] AiError),
+    #[error("The speech provider can't {0}")]
+    Unsupported(&'static str),
+}
+
+#[derive(Error, Debug)]
+pub enum VisionError {
+    #[error("Images up to {limit} MB are supported")]
+    TooLarge { limit: u32 },
+    #[error("The message has no image")]
+    NoImage,
+    #[error("Failed to fetch the image: {0}")]
+    Telegram(#[from] teloxide::RequestError),
+    #[error("Failed to download the image: {0}")]
+    Download(#[from] teloxide::DownloadError),
+    #[error(transparent)]
+    Ai(#[from] AiError),
+}
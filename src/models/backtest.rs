@@ -0,0 +1,55 @@
+use chrono::NaiveDate;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frequency::Daily => write!(f, "daily"),
+            Frequency::Weekly => write!(f, "weekly"),
+            Frequency::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    LumpSum,
+    // Dollar-cost averaging, the amount is spent on every purchase
+    Dca(Frequency),
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestRequest {
+    pub symbol: String,
+    pub start: NaiveDate,
+    pub amount: f64,
+    pub strategy: Strategy,
+}
+
+// Portfolio state at the close of one day
+#[derive(Debug, Clone)]
+pub struct EquityPoint {
+    pub date: NaiveDate,
+    pub invested: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestResult {
+    pub request: BacktestRequest,
+    pub purchases: usize,
+    pub quantity: f64,
+    pub invested: f64,
+    pub final_value: f64,
+    pub final_price: f64,
+    // Largest peak to trough fall in percent
+    pub max_drawdown: f64,
+    pub equity: Vec<EquityPoint>,
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use teloxide::types::MessageId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatTurn {
+    pub fn new(role: ChatRole, content: &str) -> Self {
+        Self {
+            role,
+            content: content.to_string(),
+        }
+    }
+}
+
+// Messages exchanged with the model in one chat, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    pub turns: Vec<ChatTurn>,
+    // Compact summary of turns that no longer fit the context budget
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub updated_at: i64,
+    // Bumped whenever turns are taken away, so a compaction started before that leaves the history alone
+    #[serde(default)]
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextPolicy {
+    // Forget the oldest turns
+    Truncate,
+    // Fold the oldest turns into the rolling summary
+    #[default]
+    Summarize,
+}
+
+impl std::fmt::Display for ContextPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContextPolicy::Truncate => write!(f, "truncate"),
+            ContextPolicy::Summarize => write!(f, "summarize"),
+        }
+    }
+}
+
+pub const DEFAULT_CONTEXT_BUDGET: usize = 3000;
+pub const MIN_CONTEXT_BUDGET: usize = 500;
+pub const MAX_CONTEXT_BUDGET: usize = 12000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ContextSettings {
+    pub policy: ContextPolicy,
+    // Estimated tokens the history may use before older turns are compacted
+    pub budget: usize,
+}
+
+impl Default for ContextSettings {
+    fn default() -> Self {
+        Self {
+            policy: ContextPolicy::default(),
+            budget: DEFAULT_CONTEXT_BUDGET,
+        }
+    }
+}
+
+// A message of a group reply thread, linked to the message it answered
+#[derive(Debug, Clone)]
+pub struct ThreadMessage {
+    pub id: MessageId,
+    pub reply_to: Option<MessageId>,
+    pub turn: ChatTurn,
+}
@@ -0,0 +1,93 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// 24h rolling window statistics for a trading pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+    pub symbol: String,
+    pub last_price: f64,
+    pub price_change: f64,
+    pub price_change_percent: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    pub volume: f64,
+    // Volume expressed in the quote asset, comparable across pairs
+    pub quote_volume: f64,
+}
+
+// One kline, open_time is the unix time in milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub open_time: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+// Result of converting an amount between two assets through one or two Binance pairs
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub amount: Decimal,
+    pub from: String,
+    pub to: String,
+    pub rate: Decimal,
+    pub result: Decimal,
+    // Assets visited in order, e.g. ["BTC", "USDT", "TRY"]
+    pub path: Vec<String>,
+    pub pairs: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BookLevel {
+    pub price: f64,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecentTrade {
+    pub time: u64,
+    pub price: f64,
+    pub quantity: f64,
+    // The buyer was the maker, so the taker sold into the bid
+    pub buyer_maker: bool,
+}
+
+// Top of the book plus the latest trades, bids are sorted high to low and asks low to high
+#[derive(Debug, Clone)]
+pub struct MarketDepth {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+    pub trades: Vec<RecentTrade>,
+}
+
+// Indicator lines drawn over the candles, aligned with them and None during warm-up
+#[derive(Debug, Clone, Default)]
+pub struct Overlays {
+    pub sma_fast: Vec<Option<f64>>,
+    pub sma_slow: Vec<Option<f64>>,
+    pub bollinger_upper: Vec<Option<f64>>,
+    pub bollinger_lower: Vec<Option<f64>>,
+}
+
+// Latest indicator readings for a pair, crosses count candles back from the last one
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub symbol: String,
+    pub interval: String,
+    pub close: f64,
+    pub rsi: f64,
+    pub macd: f64,
+    pub macd_signal: f64,
+    pub macd_histogram: f64,
+    pub macd_cross: Option<(usize, bool)>,
+    pub sma_fast: f64,
+    pub sma_slow: f64,
+    pub sma_cross: Option<(usize, bool)>,
+    pub ema_fast: f64,
+    pub ema_slow: f64,
+    pub bollinger_upper: f64,
+    pub bollinger_middle: f64,
+    pub bollinger_lower: f64,
+}
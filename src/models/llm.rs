@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::conversation::{ChatRole, ChatTurn};
+
+// Where completions come from, picked with LLM_PROVIDER
+#[derive(Debug, Clone)]
+pub enum Provider {
+    // Any server speaking the OpenAI chat completions API, such as OpenAI itself, llama.cpp, Ollama or
+    // an Azure deployment with LLM_API_VERSION set
+    OpenAiCompatible {
+        base_url: String,
+        api_key: Option<String>,
+    },
+    // Deterministic canned answers without any network access, for development and tests.
+    // With a tool set it asks for that tool whenever tools are offered
+    Mock {
+        tool: Option<String>,
+    },
+}
+
+// Message in the OpenAI chat completions format, including tool calls and their results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: Option<MessageContent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl WireMessage {
+    pub fn assistant_calls(content: &str, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: (!content.is_empty()).then(|| MessageContent::Text(content.to_string())),
+            tool_calls,
+            tool_call_id: None,
+        }
+    }
+
+    pub fn tool_result(tool_call_id: &str, content: String) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: Some(MessageContent::Text(content)),
+            tool_calls: Vec::new(),
+            tool_call_id: Some(tool_call_id.to_string()),
+        }
+    }
+
+    // A question about an image, passed inline as a data URL
+    pub fn user_with_image(text: &str, image_url: String) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: Some(MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: text.to_string(),
+                },
+                ContentPart::ImageUrl {
+                    image_url: ImageUrl { url: image_url },
+                },
+            ])),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.content
+            .as_ref()
+            .map(MessageContent::text)
+            .unwrap_or_default()
+    }
+}
+
+// Plain text, or text and images for vision models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    pub fn images(&self) -> usize {
+        match self {
+            MessageContent::Text(_) => 0,
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter(|part| matches!(part, ContentPart::ImageUrl { .. }))
+                .count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+impl From<&ChatTurn> for WireMessage {
+    fn from(turn: &ChatTurn) -> Self {
+        let role = match turn.role {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        };
+
+        Self {
+            role: role.to_string(),
+            content: Some(MessageContent::Text(turn.content.clone())),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    // JSON encoded arguments, exactly as the model produced them
+    pub arguments: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompletionRequest<'a> {
+    pub model: &'a str,
+    pub messages: &'a [WireMessage],
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "<[Value]>::is_empty")]
+    pub tools: &'a [Value],
+}
+
+// Asks for a final chunk carrying the token usage of a streamed completion
+#[derive(Debug, Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompletionResponse {
+    pub choices: Vec<ResponseChoice>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResponseChoice {
+    pub message: WireMessage,
+}
+
+// One server-sent event of a streamed completion
+#[derive(Debug, Deserialize)]
+pub struct CompletionChunk {
+    #[serde(default)]
+    pub choices: Vec<ChunkChoice>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChunkChoice {
+    #[serde(default)]
+    pub delta: ChunkDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ChunkDelta {
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+// Tool calls arrive in pieces, the index says which call a piece belongs to
+#[derive(Debug, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<FunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+// Everything the model produced in one request
+#[derive(Debug, Default)]
+pub struct Completion {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: TokenUsage,
+}
+
+// Answer of the /models endpoint
+#[derive(Debug, Deserialize)]
+pub struct ModelList {
+    pub data: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+}
@@ -1,4 +1,17 @@
+pub mod alert;
 pub mod assets;
+pub mod backtest;
+pub mod conversation;
+pub mod crypto;
+pub mod llm;
+pub mod moderation;
+pub mod movie;
 pub mod orders;
+pub mod paper;
+pub mod persona;
+pub mod portfolio;
+pub mod settings;
 pub mod soccer;
-pub mod movie;
\ No newline at end of file
+pub mod speech;
+pub mod summary;
+pub mod usage;
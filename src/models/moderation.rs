@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    // Refuse the whole message
+    Block,
+    // Replace the matching part and let the rest through
+    Redact,
+}
+
+// A local rule, the pattern is a regular expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationRule {
+    pub name: String,
+    pub pattern: String,
+    pub action: ModerationAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // Text on its way to the model
+    Input,
+    // The model's answer on its way to the chat
+    Output,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Input => write!(f, "input"),
+            Direction::Output => write!(f, "output"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Verdict {
+    Allowed,
+    // The cleaned text and the rules that changed it
+    Redacted { text: String, reasons: Vec<String> },
+    Blocked { reasons: Vec<String> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModerationRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationResponse {
+    pub results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    #[serde(default)]
+    pub categories: HashMap<String, bool>,
+}
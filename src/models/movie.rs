@@ -7,18 +7,19 @@ pub struct TrendingMovieApiResponse {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TrendingMovieResult {
-    pub backdrop_path: String,
+    pub backdrop_path: Option<String>,
     pub id: i32,
     pub title: String,
     pub original_title: String,
     pub overview: String,
-    pub poster_path: String,
+    pub poster_path: Option<String>,
     pub media_type: Option<String>,
     pub adult: bool,
     pub original_language: String,
     pub popularity: f64,
+    #[serde(default)]
     pub release_date: String,
     pub video: bool,
     pub vote_average: f64,
     pub vote_count: u32,
-}
\ No newline at end of file
+}
@@ -21,6 +21,7 @@ pub enum State {
     HandleCrypto {
         message: String,
     },
+    ChatWithAi,
 }
 
 #[derive(BotCommands, Clone)]
@@ -29,4 +30,68 @@ pub enum Command {
     Help,
     Start,
     Cancel,
+    #[command(description = "show or hide adult titles: /adult on|off")]
+    Adult(String),
+    #[command(description = "search for a movie: /movie <title>")]
+    Movie(String),
+    #[command(description = "set the language for movie results: /language de|pt-BR|auto")]
+    Language(String),
+    #[command(
+        description = "manage price alerts: /alert BTCUSDT above 70000, /alert ETH moves 5% in 1h, /alert list, /alert delete <id>"
+    )]
+    Alert(String),
+    #[command(description = "record a purchase: /buy BTCUSDT 0.5 [price] [YYYY-MM-DD]")]
+    Buy(String),
+    #[command(description = "record a sale: /sell BTCUSDT 0.5 [price] [YYYY-MM-DD]")]
+    Sell(String),
+    #[command(description = "show your portfolio, /portfolio export for a csv ledger")]
+    Portfolio(String),
+    #[command(description = "add pairs to your watchlist: /watch BTC ETH")]
+    Watch(String),
+    #[command(description = "remove pairs from your watchlist: /unwatch ETH")]
+    Unwatch(String),
+    #[command(description = "show your watchlist")]
+    Watchlist,
+    #[command(description = "show the biggest 24h gainers, losers and volume")]
+    Movers,
+    #[command(description = "convert between assets: /convert 0.5 BTC EUR")]
+    Convert(String),
+    #[command(description = "show order book depth and recent trades: /book BTCUSDT [levels]")]
+    Book(String),
+    #[command(
+        description = "paper trade with 10,000 virtual USDT: /paper buy BTC 0.01 [limit], /paper sell, orders, cancel <id>, history, reset"
+    )]
+    Paper(String),
+    #[command(description = "rank this group's paper traders by account value")]
+    Leaderboard,
+    #[command(description = "technical indicators for a pair: /ta BTCUSDT 4h [chart]")]
+    Ta(String),
+    #[command(
+        description = "what if you had bought: /backtest BTC 2022-01-01 100 weekly|monthly|daily|once"
+    )]
+    Backtest(String),
+    #[command(description = "forget the AI conversation in this chat")]
+    Reset,
+    #[command(
+        description = "choose how the AI behaves: /persona pundit, /persona custom <instructions>, /persona default"
+    )]
+    Persona(String),
+    #[command(
+        description = "show the AI's memory usage, /context summarize|truncate or /context budget <tokens> to tune it"
+    )]
+    Context(String),
+    #[command(
+        description = "show your AI usage and cost, admins can set /usage limit daily|monthly <tokens> or /usage limit default"
+    )]
+    Usage(String),
+    #[command(
+        description = "show the AI model and the available ones, /model <name> or /model default to change it"
+    )]
+    Model(String),
+    #[command(
+        description = "summarize the group's recent messages (/summarize 50, admins: /summarize on|off) or messages forwarded to me"
+    )]
+    Summarize(String),
+    #[command(description = "answer voice notes with voice: /voice on|off")]
+    Voice(String),
 }
@@ -0,0 +1,47 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::portfolio::TradeSide;
+
+// Resting limit order, its funds or coins are reserved until it fills or is cancelled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperOrder {
+    pub id: u64,
+    pub chat_id: i64,
+    pub side: TradeSide,
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub limit_price: Decimal,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperFill {
+    pub order_id: u64,
+    pub side: TradeSide,
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub limit: bool,
+    pub time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperAccount {
+    pub name: String,
+    // Free USDT, excluding what open buy orders reserve
+    pub balance: Decimal,
+    // Free coins per trading pair, excluding what open sell orders reserve
+    pub holdings: HashMap<String, Decimal>,
+    pub open_orders: Vec<PaperOrder>,
+    pub history: Vec<PaperFill>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PaperBook {
+    pub next_order_id: u64,
+    pub accounts: HashMap<u64, PaperAccount>,
+    // Users that traded from each group, used for the leaderboard
+    pub chat_members: HashMap<i64, Vec<u64>>,
+}
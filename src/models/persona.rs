@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+// A preset system prompt shipped in assets/data/personas.json
+#[derive(Debug, Clone, Deserialize)]
+pub struct Persona {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PersonaChoice {
+    // Id of one of the presets
+    Preset(String),
+    // System prompt written by the user or a group admin
+    Custom(String),
+}
@@ -0,0 +1,49 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub side: TradeSide,
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub date: NaiveDate,
+}
+
+// Holdings of one trading pair, valued with the average cost method
+#[derive(Debug, Clone, Default)]
+pub struct Position {
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub cost_basis: Decimal,
+    pub realized_pnl: Decimal,
+    pub market_price: Option<Decimal>,
+}
+
+impl Position {
+    pub fn market_value(&self) -> Option<Decimal> {
+        self.market_price
+            .and_then(|price| price.checked_mul(self.quantity))
+    }
+
+    pub fn unrealized_pnl(&self) -> Option<Decimal> {
+        self.market_value().map(|value| value - self.cost_basis)
+    }
+}
+
+impl fmt::Display for TradeSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeSide::Buy => write!(f, "buy"),
+            TradeSide::Sell => write!(f, "sell"),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{conversation::ContextSettings, persona::PersonaChoice, usage::QuotaLimits};
+
+pub const DEFAULT_LANGUAGE: &str = "en-US";
+
+// Per-chat preferences, changed by the user in private chats and by admins in groups
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatSettings {
+    #[serde(default)]
+    pub allow_adult: bool,
+    // BCP 47 tag such as "de" or "pt-BR", None follows the Telegram client language
+    #[serde(default)]
+    pub language: Option<String>,
+    // System prompt for AI replies, None uses the model's default behaviour
+    #[serde(default)]
+    pub persona: Option<PersonaChoice>,
+    #[serde(default)]
+    pub context: ContextSettings,
+    #[serde(default)]
+    pub quota: QuotaLimits,
+    // AI model for this chat, None uses the bot's default
+    #[serde(default)]
+    pub model: Option<String>,
+    // Groups opt in before the bot keeps their recent messages for /summarize
+    #[serde(default)]
+    pub summaries: bool,
+    // AI answers to voice notes also come as voice messages
+    #[serde(default)]
+    pub voice_replies: bool,
+}
+
+impl ChatSettings {
+    // The Telegram client's language is only used when TMDB would understand it
+    pub fn language_or(&self, client_language: Option<&str>) -> String {
+        self.language
+            .as_deref()
+            .or(client_language.filter(|tag| is_valid_language(tag)))
+            .unwrap_or(DEFAULT_LANGUAGE)
+            .to_string()
+    }
+}
+
+// Accepts "de", "pt-BR" and similar tags understood by TMDB
+pub fn is_valid_language(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next();
+
+    parts.next().is_none()
+        && language.len() == 2
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && region.is_none_or(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_alphabetic()))
+}
@@ -33,11 +33,11 @@ pub struct Event {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Score {
     // Add fields as needed, the JSON snippet shows an empty object
-    current: Option<i32>,
-    display: Option<i32>,
-    normaltime: Option<i32>,
-    period1: Option<i32>,
-    period2: Option<i32>,
+    pub current: Option<i32>,
+    pub display: Option<i32>,
+    pub normaltime: Option<i32>,
+    pub period1: Option<i32>,
+    pub period2: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,14 +55,14 @@ pub struct Team {
     pub slug: String,
     pub sport: Sport,
     #[serde(rename = "subTeams")]
-    pub sub_teams: Vec<String>,  // Assuming it's a list of strings, adjust if needed
+    pub sub_teams: Vec<String>, // Assuming it's a list of strings, adjust if needed
     #[serde(rename = "teamColors")]
     pub team_colors: TeamColors,
     #[serde(rename = "type")]
     pub team_type: i32,
     #[serde(rename = "userCount")]
-    pub user_count: Option<i32>,  // Made optional as it's not present for all teams
-    pub disabled: Option<bool>,  // Made optional as it's not present for all teams
+    pub user_count: Option<i32>, // Made optional as it's not present for all teams
+    pub disabled: Option<bool>, // Made optional as it's not present for all teams
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+// How a message reached the handlers, transcribed voice notes may be answered with voice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    Text,
+    Voice,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptionResponse {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpeechRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a str,
+    pub voice: &'a str,
+    pub response_format: &'a str,
+}
@@ -0,0 +1,10 @@
+use chrono::{DateTime, Utc};
+
+// A message kept for /summarize, only in memory so nothing said in a chat outlives a restart
+#[derive(Debug, Clone)]
+pub struct SeenMessage {
+    pub author: String,
+    pub text: String,
+    pub links: Vec<String>,
+    pub date: DateTime<Utc>,
+}
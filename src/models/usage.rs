@@ -0,0 +1,90 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_DAILY_TOKENS: u64 = 20_000;
+pub const DEFAULT_MONTHLY_TOKENS: u64 = 300_000;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // Estimated USD cost at the prices known when the request was made
+    pub cost: Decimal,
+}
+
+impl UsageTotals {
+    pub fn tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn add(&mut self, prompt_tokens: u64, completion_tokens: u64, cost: Decimal) {
+        self.requests += 1;
+        self.prompt_tokens += prompt_tokens;
+        self.completion_tokens += completion_tokens;
+        self.cost += cost;
+    }
+}
+
+// Consumption of one user or chat, the daily and monthly totals restart when their period changes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub day: Option<NaiveDate>,
+    pub today: UsageTotals,
+    // First day of the month the monthly totals belong to
+    pub month: Option<NaiveDate>,
+    pub this_month: UsageTotals,
+    pub all_time: UsageTotals,
+}
+
+impl UsageRecord {
+    pub fn roll(&mut self, today: NaiveDate) {
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.today = UsageTotals::default();
+        }
+
+        let month = today.with_day(1);
+        if self.month != month {
+            self.month = month;
+            self.this_month = UsageTotals::default();
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        today: NaiveDate,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        cost: Decimal,
+    ) {
+        self.roll(today);
+        self.today.add(prompt_tokens, completion_tokens, cost);
+        self.this_month.add(prompt_tokens, completion_tokens, cost);
+        self.all_time.add(prompt_tokens, completion_tokens, cost);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageBook {
+    pub users: HashMap<u64, UsageRecord>,
+    pub chats: HashMap<i64, UsageRecord>,
+}
+
+// Tokens each user may spend on AI requests from a chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    pub daily_tokens: u64,
+    pub monthly_tokens: u64,
+}
+
+impl Default for QuotaLimits {
+    fn default() -> Self {
+        Self {
+            daily_tokens: DEFAULT_DAILY_TOKENS,
+            monthly_tokens: DEFAULT_MONTHLY_TOKENS,
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::Utc;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use teloxide::{prelude::*, types::ChatId};
+
+use crate::{
+    models::{
+        alert::{format_window, Alert, AlertBook, AlertCondition},
+        assets::{AlertError, CryptoError},
+    },
+    service::crypto_service::{self, MAX_WINDOW_MINUTES},
+    utils::storage,
+};
+
+const ALERTS_FILE: &str = "alerts.json";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_ALERTS_PER_CHAT: usize = 20;
+
+lazy_static! {
+    static ref ALERTS: Mutex<AlertBook> = Mutex::new(storage::load(ALERTS_FILE));
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value
+        .trim_end_matches('%')
+        .replace(',', "")
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number > 0.0)
+}
+
+// "30m", "1h", "2d", or a bare number of hours
+fn parse_window(value: &str) -> Option<u32> {
+    let (amount, unit) = match value.char_indices().last()? {
+        (index, 'm') => (&value[..index], 1),
+        (index, 'h') => (&value[..index], 60),
+        (index, 'd') => (&value[..index], 1440),
+        _ => (value, 60),
+    };
+
+    amount
+        .parse::<u32>()
+        .ok()
+        .filter(|amount| *amount > 0)
+        .and_then(|amount| amount.checked_mul(unit))
+}
+
+// Understands "BTCUSDT above 70000", "btc < 60000" and "ETH moves 5% in 1h"
+pub fn parse_alert(text: &str) -> Result<(String, AlertCondition), AlertError> {
+    let invalid = || AlertError::InvalidFormat(text.trim().to_string());
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let (symbol, rest) = words.split_first().ok_or_else(invalid)?;
+    let symbol = crypto_service::normalize_symbol(symbol)?;
+
+    let condition = match rest {
+        [operator, price] if matches!(operator.as_str(), "above" | ">" | ">=") => {
+            AlertCondition::Above(parse_number(price).ok_or_else(invalid)?)
+        }
+        [operator, price] if matches!(operator.as_str(), "below" | "<" | "<=") => {
+            AlertCondition::Below(parse_number(price).ok_or_else(invalid)?)
+        }
+        [operator, percent, rest @ ..] if matches!(operator.as_str(), "moves" | "move") => {
+            let percent = parse_number(percent).ok_or_else(invalid)?;
+            let window_minutes = match rest {
+                [] => 60,
+                [keyword, window] if keyword == "in" => parse_window(window).ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            };
+            if window_minutes > MAX_WINDOW_MINUTES {
+                return Err(AlertError::WindowTooLong(MAX_WINDOW_MINUTES / 60));
+            }
+            AlertCondition::PercentMove {
+                percent,
+                window_minutes,
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    Ok((symbol, condition))
+}
+
+// The pair has to be listed on Binance, otherwise the alert could never fire
+pub async fn add(
+    chat_id: ChatId,
+    symbol: String,
+    condition: AlertCondition,
+) -> Result<Alert, AlertError> {
+    if crypto_service::tickers(std::slice::from_ref(&symbol))
+        .await?
+        .is_empty()
+    {
+        return Err(CryptoError::InvalidSymbol(symbol).into());
+    }
+
+    let mut book = ALERTS.lock().expect("alerts lock poisoned");
+
+    let count = book
+        .alerts
+        .iter()
+        .filter(|alert| alert.chat_id == chat_id.0)
+        .count();
+    if count >= MAX_ALERTS_PER_CHAT {
+        return Err(AlertError::TooMany(MAX_ALERTS_PER_CHAT));
+    }
+
+    book.next_id += 1;
+    let alert = Alert {
+        id: book.next_id,
+        chat_id: chat_id.0,
+        symbol,
+        condition,
+        created_at: Utc::now().timestamp(),
+        armed: true,
+    };
+    book.alerts.push(alert.clone());
+
+    storage::save(ALERTS_FILE, &*book)?;
+
+    Ok(alert)
+}
+
+pub fn list(chat_id: ChatId) -> Vec<Alert> {
+    ALERTS
+        .lock()
+        .expect("alerts lock poisoned")
+        .alerts
+        .iter()
+        .filter(|alert| alert.chat_id == chat_id.0)
+        .cloned()
+        .collect()
+}
+
+pub fn delete(chat_id: ChatId, id: u64) -> Result<(), AlertError> {
+    let mut book = ALERTS.lock().expect("alerts lock poisoned");
+
+    let index = book
+        .alerts
+        .iter()
+        .position(|alert| alert.id == id && alert.chat_id == chat_id.0)
+        .ok_or(AlertError::NotFound(id))?;
+    book.alerts.remove(index);
+
+    storage::save(ALERTS_FILE, &*book)?;
+
+    Ok(())
+}
+
+// Pairs with at least one alert, used to decide which prices need watching
+pub fn watched_symbols() -> Vec<String> {
+    let book = ALERTS.lock().expect("alerts lock poisoned");
+    let mut symbols: Vec<String> = book
+        .alerts
+        .iter()
+        .map(|alert| alert.symbol.clone())
+        .collect();
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+pub fn format_alert(alert: &Alert) -> String {
+    format!("#{} {} {}", alert.id, alert.symbol, alert.condition)
+}
+
+async fn evaluate(alert: &Alert, price: f64) -> Result<Option<String>, AlertError> {
+    let message = match alert.condition {
+        AlertCondition::Above(target) => (price >= target).then(|| {
+            format!(
+                "{} is above {} (now {})",
+                alert.symbol,
+                target,
+                crypto_service::format_price(price)
+            )
+        }),
+        AlertCondition::Below(target) => (price <= target).then(|| {
+            format!(
+                "{} is below {} (now {})",
+                alert.symbol,
+                target,
+                crypto_service::format_price(price)
+            )
+        }),
+        AlertCondition::PercentMove {
+            percent,
+            window_minutes,
+        } => {
+            let start = crypto_service::price_minutes_ago(&alert.symbol, window_minutes).await?;
+
+            let change = (price - start) / start * 100.0;
+            (change.abs() >= percent).then(|| {
+                format!(
+                    "{} moved {:+.2}% in {} (now {})",
+                    alert.symbol,
+                    change,
+                    format_window(window_minutes),
+                    crypto_service::format_price(price)
+                )
+            })
+        }
+    };
+
+    Ok(message)
+}
+
+async fn check_alerts(bot: &Bot) -> Result<(), AlertError> {
+    let alerts = ALERTS.lock().expect("alerts lock poisoned").alerts.clone();
+    if alerts.is_empty() {
+        return Ok(());
+    }
+
+    let prices = crypto_service::prices(&watched_symbols()).await?;
+    let mut armed = HashMap::new();
+    let mut notifications = Vec::new();
+
+    for alert in &alerts {
+        let Some(price) = prices.get(&alert.symbol) else {
+            continue;
+        };
+
+        match evaluate(alert, *price).await {
+            Ok(Some(message)) => {
+                if alert.armed {
+                    notifications.push((ChatId(alert.chat_id), alert.id, message));
+                }
+                armed.insert(alert.id, false);
+            }
+            Ok(None) => {
+                armed.insert(alert.id, true);
+            }
+            Err(err) => log::error!("failed to evaluate alert {}: {}", alert.id, err),
+        }
+    }
+
+    {
+        let mut book = ALERTS.lock().expect("alerts lock poisoned");
+        let mut changed = false;
+        for alert in book.alerts.iter_mut() {
+            if let Some(state) = armed.get(&alert.id) {
+                changed |= alert.armed != *state;
+                alert.armed = *state;
+            }
+        }
+        if changed {
+            storage::save(ALERTS_FILE, &*book)?;
+        }
+    }
+
+    for (chat_id, id, message) in notifications {
+        log::info!("alert {id} triggered: {message}");
+        if let Err(err) = bot
+            .send_message(chat_id, format!("Price alert #{id}: {message}"))
+            .await
+        {
+            log::error!("failed to deliver alert {id}: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+// Background loop started with the dispatcher, alerts fire once until their condition resets
+pub async fn monitor(bot: Bot) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = check_alerts(&bot).await {
+            log::error!("price alert check failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_price_alerts() {
+        assert_eq!(
+            parse_alert("BTCUSDT above 70,000").unwrap(),
+            ("BTCUSDT".to_string(), AlertCondition::Above(70000.0))
+        );
+        assert_eq!(
+            parse_alert("btc < 60000").unwrap(),
+            ("BTCUSDT".to_string(), AlertCondition::Below(60000.0))
+        );
+    }
+
+    #[test]
+    fn parses_move_alerts_with_windows() {
+        let window = |text: &str| match parse_alert(text).unwrap().1 {
+            AlertCondition::PercentMove { window_minutes, .. } => window_minutes,
+            condition => panic!("unexpected condition {condition:?}"),
+        };
+
+        assert_eq!(window("ETH moves 5%"), 60);
+        assert_eq!(window("ETH moves 5% in 30m"), 30);
+        assert_eq!(window("ETH moves 5% in 2d"), 2880);
+        assert_eq!(window("ETH moves 5% in 12"), 720);
+    }
+
+    #[test]
+    fn rejects_malformed_alerts() {
+        for text in [
+            "",
+            "BTC",
+            "BTC above",
+            "BTC above -5",
+            "BTC above nan",
+            "BTC moves 5% in 0h",
+            "BTC moves 5% at 1h",
+        ] {
+            assert!(
+                matches!(parse_alert(text), Err(AlertError::InvalidFormat(_))),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_windows_past_the_kline_history() {
+        assert!(parse_alert("BTC moves 5% in 999h").is_ok());
+        assert!(matches!(
+            parse_alert("BTC moves 5% in 1000h"),
+            Err(AlertError::WindowTooLong(999))
+        ));
+        // 4294967295 minutes would overflow once multiplied by the unit
+        assert!(matches!(
+            parse_alert("BTC moves 5% in 4294967295d"),
+            Err(AlertError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, Days, Months, NaiveDate, Utc};
+use tokio::task;
+
+use crate::{
+    models::{
+        assets::BacktestError,
+        backtest::{BacktestRequest, BacktestResult, EquityPoint, Frequency, Strategy},
+        crypto::Candle,
+    },
+    service::{
+        chart_service,
+        crypto_service::{self, format_price, format_quantity},
+    },
+};
+
+pub const BACKTEST_USAGE: &str =
+    "Usage: /backtest <pair> <YYYY-MM-DD> <amount> [daily|weekly|monthly|once]\n\
+    e.g. /backtest BTC 2022-01-01 100 weekly, or /backtest ETH 2021-06-01 1000 once for a lump sum";
+
+fn parse_amount(value: &str) -> Option<f64> {
+    value
+        .trim_start_matches('$')
+        .replace(',', "")
+        .parse::<f64>()
+        .ok()
+        .filter(|amount| amount.is_finite() && *amount > 0.0)
+}
+
+// "<pair> <start date> <amount> [frequency]", weekly DCA unless told otherwise
+pub fn parse_backtest(text: &str) -> Result<BacktestRequest, BacktestError> {
+    let invalid = || BacktestError::InvalidFormat(text.trim().to_string());
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let (symbol, start, amount, frequency) = match words.as_slice() {
+        [symbol, start, amount] => (symbol, start, amount, "weekly"),
+        [symbol, start, amount, frequency] => (symbol, start, amount, frequency.as_str()),
+        _ => return Err(invalid()),
+    };
+
+    let strategy = match frequency {
+        "daily" => Strategy::Dca(Frequency::Daily),
+        "weekly" => Strategy::Dca(Frequency::Weekly),
+        "monthly" => Strategy::Dca(Frequency::Monthly),
+        "once" | "lump" | "lumpsum" => Strategy::LumpSum,
+        _ => return Err(invalid()),
+    };
+
+    let start = NaiveDate::parse_from_str(start, "%Y-%m-%d").map_err(|_| invalid())?;
+    if start >= Utc::now().date_naive() {
+        return Err(BacktestError::InvalidStart(start.to_string()));
+    }
+
+    Ok(BacktestRequest {
+        symbol: crypto_service::normalize_symbol(symbol)?,
+        start,
+        amount: parse_amount(amount).ok_or_else(invalid)?,
+        strategy,
+    })
+}
+
+fn next_purchase(date: NaiveDate, frequency: Frequency) -> Option<NaiveDate> {
+    match frequency {
+        Frequency::Daily => date.checked_add_days(Days::new(1)),
+        Frequency::Weekly => date.checked_add_days(Days::new(7)),
+        Frequency::Monthly => date.checked_add_months(Months::new(1)),
+    }
+}
+
+fn candle_date(candle: &Candle) -> Option<NaiveDate> {
+    DateTime::<Utc>::from_timestamp_millis(candle.open_time).map(|time| time.date_naive())
+}
+
+// Measured on value per unit invested, so new contributions can't hide a fall in price
+fn max_drawdown(equity: &[EquityPoint]) -> f64 {
+    let mut peak = 0.0_f64;
+    let mut drawdown = 0.0_f64;
+
+    for point in equity.iter().filter(|point| point.invested > 0.0) {
+        let ratio = point.value / point.invested;
+        peak = peak.max(ratio);
+        drawdown = drawdown.max((peak - ratio) / peak * 100.0);
+    }
+
+    drawdown
+}
+
+// Purchases happen at the open of their day, the equity curve uses daily closes
+fn simulate(request: BacktestRequest, candles: &[Candle]) -> Option<BacktestResult> {
+    let mut next = Some(request.start);
+    let mut quantity = 0.0;
+    let mut invested = 0.0;
+    let mut purchases = 0;
+    let mut equity = Vec::with_capacity(candles.len());
+
+    for candle in candles {
+        let date = candle_date(candle)?;
+
+        if next.is_some_and(|next| date >= next) && candle.open > 0.0 {
+            quantity += request.amount / candle.open;
+            invested += request.amount;
+            purchases += 1;
+            next = match request.strategy {
+                Strategy::LumpSum => None,
+                Strategy::Dca(frequency) => next_purchase(date, frequency),
+            };
+        }
+
+        equity.push(EquityPoint {
+            date,
+            invested,
+            value: quantity * candle.close,
+        });
+    }
+
+    let final_point = equity.last()?;
+    let final_value = final_point.value;
+    let final_price = candles.last()?.close;
+
+    Some(BacktestResult {
+        max_drawdown: max_drawdown(&equity),
+        request,
+        purchases,
+        quantity,
+        invested,
+        final_value,
+        final_price,
+        equity,
+    })
+}
+
+pub async fn run(request: BacktestRequest) -> Result<BacktestResult, BacktestError> {
+    let start_time = request
+        .start
+        .and_hms_opt(0, 0, 0)
+        .map(|time| time.and_utc().timestamp_millis())
+        .unwrap_or_default();
+
+    let candles = crypto_service::daily_klines_since(&request.symbol, start_time).await?;
+    let symbol = request.symbol.clone();
+
+    simulate(request, &candles)
+        .filter(|result| result.purchases > 0)
+        .ok_or(BacktestError::NoHistory(symbol))
+}
+
+fn describe(strategy: Strategy, amount: f64) -> String {
+    match strategy {
+        Strategy::LumpSum => format!("lump sum of {}", format_price(amount)),
+        Strategy::Dca(frequency) => format!("{} {frequency}", format_price(amount)),
+    }
+}
+
+pub fn format_result(result: &BacktestResult) -> String {
+    let request = &result.request;
+    let profit = result.final_value - result.invested;
+    let profit_percent = if result.invested > 0.0 {
+        profit / result.invested * 100.0
+    } else {
+        0.0
+    };
+    let first_date = result
+        .equity
+        .first()
+        .map(|point| point.date)
+        .unwrap_or(request.start);
+    let last_date = result
+        .equity
+        .last()
+        .map(|point| point.date)
+        .unwrap_or(request.start);
+
+    let mut message = format!(
+        "What if you bought {} {} since {}?\n\n",
+        request.symbol,
+        describe(request.strategy, request.amount),
+        first_date
+    );
+    message.push_str(&format!("Purchases: {}\n", result.purchases));
+    message.push_str(&format!(
+        "Total Invested: {}\n",
+        format_price(result.invested)
+    ));
+    message.push_str(&format!(
+        "Coins Bought: {}\n",
+        format_quantity(result.quantity)
+    ));
+    message.push_str(&format!(
+        "Average Price: {}\n",
+        format_price(result.invested / result.quantity)
+    ));
+    message.push_str(&format!(
+        "Final Value: {} on {} (price {})\n",
+        format_price(result.final_value),
+        last_date,
+        format_price(result.final_price)
+    ));
+    message.push_str(&format!(
+        "Profit: {} ({:+.2}%)\n",
+        format_price(profit),
+        profit_percent
+    ));
+    message.push_str(&format!("Max Drawdown: {:.2}%\n", result.max_drawdown));
+    message.push_str("\nPast performance does not predict future results.");
+
+    message
+}
+
+pub async fn equity_chart(result: &BacktestResult) -> Result<Vec<u8>, BacktestError> {
+    let title = format!(
+        "{} {}",
+        result.request.symbol,
+        describe(result.request.strategy, result.request.amount)
+    );
+    let equity = result.equity.clone();
+
+    let png = task::spawn_blocking(move || chart_service::render_equity(&title, &equity)).await??;
+
+    Ok(png)
+}
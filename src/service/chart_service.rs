@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use plotters::{
+    prelude::*,
+    style::{register_font, FontStyle},
+};
+use std::sync::Once;
+
+use crate::{
+    models::{
+        assets::ChartError,
+        backtest::EquityPoint,
+        crypto::{Candle, Overlays},
+    },
+    service::crypto_service::{compact_number, format_price},
+};
+
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 640;
+const FONT: &str = "sans-serif";
+
+static FONT_INIT: Once = Once::new();
+
+// plotters has no system font lookup in this build, so the bundled font is registered once
+fn init_font() {
+    FONT_INIT.call_once(|| {
+        let bytes = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+        if register_font(FONT, FontStyle::Normal, bytes).is_err() {
+            log::error!("failed to register the chart font");
+        }
+    });
+}
+
+fn time_label(candles: &[Candle], x: f64, interval: &str) -> String {
+    let index = x.round();
+    if index < 0.0 {
+        return String::new();
+    }
+
+    let pattern = match interval {
+        "1h" | "4h" => "%d %b %H:%M",
+        _ => "%d %b %y",
+    };
+
+    candles
+        .get(index as usize)
+        .and_then(|candle| DateTime::<Utc>::from_timestamp_millis(candle.open_time))
+        .map(|time| time.format(pattern).to_string())
+        .unwrap_or_default()
+}
+
+fn overlay_points(series: &[Option<f64>]) -> Vec<(f64, f64)> {
+    series
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| value.map(|value| (index as f64, value)))
+        .collect()
+}
+
+fn draw_candles(
+    buffer: &mut [u8],
+    symbol: &str,
+    interval: &str,
+    candles: &[Candle],
+    overlays: Option<&Overlays>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = BitMapBackend::with_buffer(buffer, (WIDTH, HEIGHT)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let (upper, lower) = root.split_vertically(HEIGHT * 3 / 4);
+
+    // Bands can reach past the candles, so they widen the price range too
+    let band_values = overlays
+        .into_iter()
+        .flat_map(|overlays| {
+            overlays
+                .bollinger_upper
+                .iter()
+                .chain(&overlays.bollinger_lower)
+        })
+        .flatten();
+    let (low, high) = candles
+        .iter()
+        .flat_map(|candle| [candle.low, candle.high])
+        .chain(band_values.copied())
+        .fold((f64::MAX, f64::MIN), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    let padding = ((high - low) * 0.05).max(high * 0.001);
+    let max_volume = candles
+        .iter()
+        .map(|candle| candle.volume)
+        .fold(0.0, f64::max);
+
+    let x_range = -1.0..candles.len() as f64;
+    let candle_width = ((WIDTH - 100) / candles.len() as u32 * 7 / 10).max(1);
+
+    let mut price_chart = ChartBuilder::on(&upper)
+        .caption(format!("{symbol} ({interval})"), (FONT, 24))
+        .margin(10)
+        .x_label_area_size(0)
+        .y_label_area_size(80)
+        .build_cartesian_2d(x_range.clone(), (low - padding)..(high + padding))?;
+
+    price_chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .y_labels(8)
+        .y_label_formatter(&|price| format_price(*price))
+        .label_style((FONT, 13))
+        .draw()?;
+
+    price_chart.draw_series(candles.iter().enumerate().map(|(index, candle)| {
+        CandleStick::new(
+            index as f64,
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            GREEN.filled(),
+            RED.filled(),
+            candle_width,
+        )
+    }))?;
+
+    if let Some(overlays) = overlays {
+        let band_color = BLUE.mix(0.5);
+        price_chart
+            .draw_series(LineSeries::new(
+                overlay_points(&overlays.bollinger_upper),
+                band_color,
+            ))?
+            .label("Bollinger bands")
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], band_color));
+        price_chart.draw_series(LineSeries::new(
+            overlay_points(&overlays.bollinger_lower),
+            band_color,
+        ))?;
+        price_chart
+            .draw_series(LineSeries::new(
+                overlay_points(&overlays.sma_fast),
+                MAGENTA.stroke_width(2),
+            ))?
+            .label("Fast SMA")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], MAGENTA.stroke_width(2)));
+        price_chart
+            .draw_series(LineSeries::new(
+                overlay_points(&overlays.sma_slow),
+                BLACK.stroke_width(2),
+            ))?
+            .label("Slow SMA")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK.stroke_width(2)));
+
+        price_chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .label_font((FONT, 13))
+            .draw()?;
+    }
+
+    let mut volume_chart = ChartBuilder::on(&lower)
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(80)
+        .build_cartesian_2d(x_range, 0.0..(max_volume * 1.1).max(1.0))?;
+
+    volume_chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(6)
+        .x_label_formatter(&|x| time_label(candles, *x, interval))
+        .y_labels(3)
+        .y_label_formatter(&|volume| compact_number(*volume))
+        .label_style((FONT, 13))
+        .draw()?;
+
+    volume_chart.draw_series(candles.iter().enumerate().map(|(index, candle)| {
+        let color = if candle.close >= candle.open {
+            GREEN
+        } else {
+            RED
+        };
+        let x = index as f64;
+        Rectangle::new(
+            [(x - 0.35, 0.0), (x + 0.35, candle.volume)],
+            color.mix(0.6).filled(),
+        )
+    }))?;
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_equity(
+    buffer: &mut [u8],
+    title: &str,
+    equity: &[EquityPoint],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = BitMapBackend::with_buffer(buffer, (WIDTH, HEIGHT)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let high = equity
+        .iter()
+        .flat_map(|point| [point.value, point.invested])
+        .fold(0.0, f64::max);
+    let date_label = |x: &f64| {
+        equity
+            .get(x.round().max(0.0) as usize)
+            .map(|point| point.date.format("%d %b %y").to_string())
+            .unwrap_or_default()
+    };
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, (FONT, 24))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(80)
+        .build_cartesian_2d(
+            0.0..(equity.len() as f64 - 1.0).max(1.0),
+            0.0..(high * 1.05).max(1.0),
+        )?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(6)
+        .x_label_formatter(&date_label)
+        .y_labels(8)
+        .y_label_formatter(&|value| compact_number(*value))
+        .label_style((FONT, 13))
+        .draw()?;
+
+    let points = |field: fn(&EquityPoint) -> f64| {
+        equity
+            .iter()
+            .enumerate()
+            .map(move |(index, point)| (index as f64, field(point)))
+    };
+
+    chart
+        .draw_series(LineSeries::new(
+            points(|point| point.value),
+            BLUE.stroke_width(2),
+        ))?
+        .label("Value")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE.stroke_width(2)));
+    chart
+        .draw_series(LineSeries::new(
+            points(|point| point.invested),
+            BLACK.mix(0.6).stroke_width(2),
+        ))?
+        .label("Invested")
+        .legend(|(x, y)| {
+            PathElement::new(vec![(x, y), (x + 20, y)], BLACK.mix(0.6).stroke_width(2))
+        });
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .label_font((FONT, 13))
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+// Candlesticks on top and volume bars below, returned as png bytes
+pub fn render_candlestick(
+    symbol: &str,
+    interval: &str,
+    candles: &[Candle],
+    overlays: Option<&Overlays>,
+) -> Result<Vec<u8>, ChartError> {
+    if candles.is_empty() {
+        return Err(ChartError::NoData);
+    }
+
+    init_font();
+
+    let mut buffer = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    draw_candles(&mut buffer, symbol, interval, candles, overlays)
+        .map_err(|err| ChartError::Draw(err.to_string()))?;
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png).write_image(&buffer, WIDTH, HEIGHT, ColorType::Rgb8)?;
+
+    Ok(png)
+}
+
+// Portfolio value against the amount invested over time, returned as png bytes
+pub fn render_equity(title: &str, equity: &[EquityPoint]) -> Result<Vec<u8>, ChartError> {
+    if equity.is_empty() {
+        return Err(ChartError::NoData);
+    }
+
+    init_font();
+
+    let mut buffer = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    draw_equity(&mut buffer, title, equity).map_err(|err| ChartError::Draw(err.to_string()))?;
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png).write_image(&buffer, WIDTH, HEIGHT, ColorType::Rgb8)?;
+
+    Ok(png)
+}
@@ -0,0 +1,202 @@
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    models::{assets::ConversionError, crypto::Conversion},
+    service::crypto_service::{self, to_decimal},
+};
+
+// Assets tried, in order, when there is no direct market between two assets
+const BRIDGE_ASSETS: [&str; 6] = ["USDT", "USDC", "FDUSD", "BTC", "ETH", "BNB"];
+// Binance has no USD markets, the dollar is bridged through USDT
+const USD_STABLECOIN: &str = "USDT";
+
+fn asset_alias(asset: &str) -> String {
+    match asset.to_uppercase().as_str() {
+        "USD" | "$" => USD_STABLECOIN.to_string(),
+        asset => asset.to_string(),
+    }
+}
+
+// Rate of one `from` in `to`, using the direct pair or the inverse of the opposite pair
+fn direct_rate(prices: &HashMap<String, f64>, from: &str, to: &str) -> Option<(Decimal, String)> {
+    let direct = format!("{from}{to}");
+    if let Some(price) = prices.get(&direct).copied().and_then(to_decimal) {
+        return Some((price, direct));
+    }
+
+    let inverse = format!("{to}{from}");
+    prices
+        .get(&inverse)
+        .copied()
+        .and_then(to_decimal)
+        .filter(|price| !price.is_zero())
+        .and_then(|price| Decimal::ONE.checked_div(price))
+        .map(|rate| (rate, inverse))
+}
+
+fn find_route(
+    prices: &HashMap<String, f64>,
+    from: &str,
+    to: &str,
+) -> Option<(Decimal, Vec<String>, Vec<String>)> {
+    if from == to {
+        return Some((Decimal::ONE, vec![from.to_string()], Vec::new()));
+    }
+
+    if let Some((rate, pair)) = direct_rate(prices, from, to) {
+        return Some((rate, vec![from.to_string(), to.to_string()], vec![pair]));
+    }
+
+    BRIDGE_ASSETS
+        .iter()
+        .filter(|bridge| **bridge != from && **bridge != to)
+        .find_map(|bridge| {
+            let (first_rate, first_pair) = direct_rate(prices, from, bridge)?;
+            let (second_rate, second_pair) = direct_rate(prices, bridge, to)?;
+            Some((
+                first_rate.checked_mul(second_rate)?,
+                vec![from.to_string(), bridge.to_string(), to.to_string()],
+                vec![first_pair, second_pair],
+            ))
+        })
+}
+
+// "0.5 BTC EUR" or "0.5 btc to eur"
+pub fn parse_conversion(text: &str) -> Result<(Decimal, String, String), ConversionError> {
+    let invalid = || ConversionError::InvalidFormat(text.trim().to_string());
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|word| !word.eq_ignore_ascii_case("to") && !word.eq_ignore_ascii_case("in"))
+        .collect();
+
+    match words.as_slice() {
+        [amount, from, to] => {
+            let amount = Decimal::from_str(&amount.replace(',', ""))
+                .ok()
+                .filter(|amount| amount.is_sign_positive() && !amount.is_zero())
+                .ok_or_else(invalid)?;
+            Ok((amount, asset_alias(from), asset_alias(to)))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub async fn convert(text: &str) -> Result<Conversion, ConversionError> {
+    let (amount, from, to) = parse_conversion(text)?;
+    log::info!("Converting {amount} {from} to {to}");
+
+    let prices = crypto_service::all_prices().await?;
+    let (rate, path, pairs) = find_route(&prices, &from, &to)
+        .ok_or_else(|| ConversionError::NoRoute(from.clone(), to.clone()))?;
+    let result = amount
+        .checked_mul(rate)
+        .ok_or_else(|| ConversionError::TooLarge(text.trim().to_string()))?;
+
+    Ok(Conversion {
+        amount,
+        result,
+        rate,
+        from,
+        to,
+        path,
+        pairs,
+    })
+}
+
+pub fn format_conversion(conversion: &Conversion) -> String {
+    let mut message = format!(
+        "{} {} = {} {}\n",
+        conversion.amount.normalize(),
+        conversion.from,
+        conversion.result.round_dp(8).normalize(),
+        conversion.to
+    );
+    message.push_str(&format!(
+        "Rate: 1 {} = {} {}\n",
+        conversion.from,
+        conversion.rate.round_dp(8).normalize(),
+        conversion.to
+    ));
+    message.push_str(&format!("Path: {}\n", conversion.path.join(" -> ")));
+
+    if !conversion.pairs.is_empty() {
+        message.push_str(&format!("Markets: {}\n", conversion.pairs.join(", ")));
+    }
+    if conversion.path.iter().any(|asset| asset == USD_STABLECOIN) {
+        message.push_str(&format!("{USD_STABLECOIN} is treated as 1 USD.\n"));
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_map(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
+        pairs
+            .iter()
+            .map(|(symbol, price)| (symbol.to_string(), *price))
+            .collect()
+    }
+
+    #[test]
+    fn parses_conversions() {
+        let (amount, from, to) = parse_conversion("1,000.5 usd to eur").unwrap();
+
+        assert_eq!(amount, Decimal::from_str("1000.5").unwrap());
+        assert_eq!((from.as_str(), to.as_str()), ("USDT", "EUR"));
+        assert!(parse_conversion("0.5 BTC in ETH").is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_conversions() {
+        for text in ["", "BTC EUR", "0 BTC EUR", "-1 BTC EUR", "1 BTC EUR USD"] {
+            assert!(
+                matches!(
+                    parse_conversion(text),
+                    Err(ConversionError::InvalidFormat(_))
+                ),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn uses_direct_and_inverse_pairs() {
+        let prices = price_map(&[("BTCUSDT", 50000.0)]);
+
+        let (rate, path, pairs) = find_route(&prices, "BTC", "USDT").unwrap();
+        assert_eq!(rate, Decimal::from(50000));
+        assert_eq!(path, ["BTC", "USDT"]);
+        assert_eq!(pairs, ["BTCUSDT"]);
+
+        let (rate, _, pairs) = find_route(&prices, "USDT", "BTC").unwrap();
+        assert_eq!(rate, Decimal::ONE / Decimal::from(50000));
+        assert_eq!(pairs, ["BTCUSDT"]);
+    }
+
+    #[test]
+    fn bridges_through_a_common_asset() {
+        let prices = price_map(&[("SOLUSDT", 100.0), ("EURUSDT", 1.25)]);
+
+        let (rate, path, pairs) = find_route(&prices, "SOL", "EUR").unwrap();
+
+        assert_eq!(rate, Decimal::from(80));
+        assert_eq!(path, ["SOL", "USDT", "EUR"]);
+        assert_eq!(pairs, ["SOLUSDT", "EURUSDT"]);
+    }
+
+    #[test]
+    fn same_asset_and_missing_routes() {
+        let prices = price_map(&[("BTCUSDT", 50000.0)]);
+
+        assert_eq!(
+            find_route(&prices, "BTC", "BTC").map(|route| route.0),
+            Some(Decimal::ONE)
+        );
+        assert!(find_route(&prices, "BTC", "DOGE").is_none());
+        assert!(find_route(&price_map(&[("BTCUSDT", 0.0)]), "USDT", "BTC").is_none());
+    }
+}
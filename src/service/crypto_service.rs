@@ -1,8 +1,622 @@
-#[tokio::main]
-pub async fn main() {
-    
+use binance::{
+    api::Binance,
+    market::Market,
+    model::{DayTickerEvent, KlineSummaries, PriceStats, Prices},
+    websockets::{WebSockets, WebsocketEvent},
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::task;
+
+use crate::{
+    models::{
+        assets::CryptoError,
+        crypto::{BookLevel, Candle, MarketDepth, RecentTrade, Ticker},
+    },
+    service::{alert_service, chart_service, watchlist_service},
+};
+
+const QUOTE_ASSETS: [&str; 6] = ["USDT", "USDC", "FDUSD", "BTC", "ETH", "BNB"];
+const DEFAULT_QUOTE: &str = "USDT";
+pub const CHART_INTERVALS: [&str; 4] = ["1h", "4h", "1d", "1w"];
+pub const DEFAULT_CHART_INTERVAL: &str = "4h";
+const CHART_CANDLES: u16 = 90;
+const KLINE_PAGE: u16 = 1000;
+// Longest move an alert can watch, one page of hourly klines plus the current candle
+pub const MAX_WINDOW_MINUTES: u32 = (KLINE_PAGE as u32 - 1) * 60;
+// Binance only accepts these depth limits
+const DEPTH_LIMITS: [u64; 5] = [5, 10, 20, 50, 100];
+pub const DEFAULT_DEPTH: usize = 10;
+const RECENT_TRADES: u16 = 10;
+
+// Websocket ticker updates arrive every second, anything older means the stream is down
+const CACHE_MAX_AGE: Duration = Duration::from_secs(60);
+const SUBSCRIPTION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref TICKER_CACHE: RwLock<HashMap<String, (Ticker, Instant)>> =
+        RwLock::new(HashMap::new());
+    // Start prices of alert windows together with the open time of the candle they were read from
+    static ref WINDOW_STARTS: RwLock<HashMap<(String, u32), (i64, f64)>> =
+        RwLock::new(HashMap::new());
+}
+
+// The binance client is blocking, so every call runs on the blocking pool
+async fn market_call<T, F>(call: F) -> Result<T, CryptoError>
+where
+    F: FnOnce(&Market) -> binance::errors::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(move || {
+        let market: Market = Binance::new(None, None);
+        call(&market).map_err(|err| CryptoError::Binance(err.to_string()))
+    })
+    .await?
+}
+
+// "btc", "BTC/USDT" and "btcusdt" all become "BTCUSDT"
+pub fn normalize_symbol(input: &str) -> Result<String, CryptoError> {
+    let symbol: String = input
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '/' | '-' | '_' | ' '))
+        .collect::<String>()
+        .to_uppercase();
+
+    if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(CryptoError::InvalidSymbol(input.trim().to_string()));
+    }
+
+    let has_quote = QUOTE_ASSETS
+        .iter()
+        .any(|quote| symbol.len() > quote.len() && symbol.ends_with(quote));
+
+    if has_quote {
+        Ok(symbol)
+    } else {
+        Ok(format!("{symbol}{DEFAULT_QUOTE}"))
+    }
+}
+
+fn ticker_from_stats(stats: PriceStats) -> Ticker {
+    let weighted_average: f64 = stats.weighted_avg_price.parse().unwrap_or_default();
+
+    Ticker {
+        symbol: stats.symbol,
+        last_price: stats.last_price,
+        price_change: stats.price_change.parse().unwrap_or_default(),
+        price_change_percent: stats.price_change_percent.parse().unwrap_or_default(),
+        high_price: stats.high_price,
+        low_price: stats.low_price,
+        volume: stats.volume,
+        quote_volume: stats.volume * weighted_average,
+    }
+}
+
+fn ticker_from_event(event: DayTickerEvent) -> Ticker {
+    let last_price: f64 = event.current_close.parse().unwrap_or_default();
+
+    Ticker {
+        symbol: event.symbol,
+        last_price,
+        price_change: event.price_change.parse().unwrap_or_default(),
+        price_change_percent: event.price_change_percent.parse().unwrap_or_default(),
+        high_price: event.high.parse().unwrap_or_default(),
+        low_price: event.low.parse().unwrap_or_default(),
+        volume: event.volume.parse().unwrap_or_default(),
+        quote_volume: event.quote_volume.parse().unwrap_or_default(),
+    }
+}
+
+fn cached_ticker(symbol: &str) -> Option<Ticker> {
+    TICKER_CACHE
+        .read()
+        .expect("ticker cache lock poisoned")
+        .get(symbol)
+        .filter(|(_, updated)| updated.elapsed() < CACHE_MAX_AGE)
+        .map(|(ticker, _)| ticker.clone())
+}
+
+// Pairs somebody watches or has an alert on, these are kept live over the websocket
+fn subscribed_symbols() -> Vec<String> {
+    let mut symbols = alert_service::watched_symbols();
+    symbols.extend(watchlist_service::watched_symbols());
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+// Blocks until `running` is cleared or the connection drops
+fn stream_tickers(symbols: &[String], running: &AtomicBool) -> binance::errors::Result<()> {
+    let endpoints: Vec<String> = symbols
+        .iter()
+        .map(|symbol| format!("{}@ticker", symbol.to_lowercase()))
+        .collect();
+
+    let mut web_socket = WebSockets::new(|event: WebsocketEvent| {
+        if let WebsocketEvent::DayTicker(event) = event {
+            let ticker = ticker_from_event(event);
+            TICKER_CACHE
+                .write()
+                .expect("ticker cache lock poisoned")
+                .insert(ticker.symbol.clone(), (ticker, Instant::now()));
+        }
+        Ok(())
+    });
+
+    web_socket.connect_multiple_streams(&endpoints)?;
+    let result = web_socket.event_loop(running);
+    web_socket.disconnect()?;
+    result
+}
+
+// Background task keeping the ticker cache fed, reconnecting whenever the subscribed pairs change
+pub async fn market_data() {
+    loop {
+        let symbols = subscribed_symbols();
+
+        if symbols.is_empty() {
+            tokio::time::sleep(SUBSCRIPTION_CHECK_INTERVAL).await;
+            continue;
+        }
+
+        log::info!("Streaming tickers for {} pairs", symbols.len());
+        let running = Arc::new(AtomicBool::new(true));
+
+        let stream = {
+            let symbols = symbols.clone();
+            let running = running.clone();
+            task::spawn_blocking(move || {
+                stream_tickers(&symbols, &running)
+                    .map_err(|err| CryptoError::Binance(err.to_string()))
+            })
+        };
+
+        let watcher = {
+            let running = running.clone();
+            async move {
+                while running.load(Ordering::Relaxed) {
+                    tokio::time::sleep(SUBSCRIPTION_CHECK_INTERVAL).await;
+                    if subscribed_symbols() != symbols {
+                        running.store(false, Ordering::Relaxed);
+                    }
+                }
+            }
+        };
+
+        let (result, _) = tokio::join!(
+            async {
+                let result = stream.await;
+                running.store(false, Ordering::Relaxed);
+                result
+            },
+            watcher
+        );
+
+        match result {
+            Ok(Ok(())) => log::info!("Ticker stream closed, resubscribing"),
+            Ok(Err(err)) => log::error!("Ticker stream failed: {}", err),
+            Err(err) => log::error!("Ticker stream task failed: {}", err),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+pub async fn ticker(symbol: &str) -> Result<Ticker, CryptoError> {
+    if let Some(ticker) = cached_ticker(symbol) {
+        return Ok(ticker);
+    }
+
+    log::info!("Fetching 24h ticker for {symbol}");
+    let symbol = symbol.to_string();
+
+    let stats = market_call(move |market| market.get_24h_price_stats(symbol)).await?;
+
+    Ok(ticker_from_stats(stats))
+}
+
+// 24h statistics for every pair listed on Binance
+pub async fn all_tickers() -> Result<Vec<Ticker>, CryptoError> {
+    log::info!("Fetching 24h tickers for all pairs");
+    let stats = market_call(|market| market.get_all_24h_price_stats()).await?;
+
+    Ok(stats.into_iter().map(ticker_from_stats).collect())
+}
+
+// Tickers for the given pairs in the order they were asked for
+pub async fn tickers(symbols: &[String]) -> Result<Vec<Ticker>, CryptoError> {
+    let cached: Vec<Ticker> = symbols
+        .iter()
+        .filter_map(|symbol| cached_ticker(symbol))
+        .collect();
+    if cached.len() == symbols.len() {
+        return Ok(cached);
+    }
+
+    let mut all = all_tickers().await?;
+
+    let tickers = symbols
+        .iter()
+        .filter_map(|symbol| {
+            let index = all.iter().position(|ticker| &ticker.symbol == symbol)?;
+            Some(all.swap_remove(index))
+        })
+        .collect();
+
+    Ok(tickers)
+}
+
+// Biggest gainers, biggest losers and the most traded pairs quoted in USDT
+pub async fn top_movers(
+    count: usize,
+) -> Result<(Vec<Ticker>, Vec<Ticker>, Vec<Ticker>), CryptoError> {
+    let mut markets: Vec<Ticker> = all_tickers()
+        .await?
+        .into_iter()
+        .filter(|ticker| ticker.symbol.ends_with(DEFAULT_QUOTE) && ticker.quote_volume > 0.0)
+        .collect();
+
+    markets.sort_by(|a, b| b.price_change_percent.total_cmp(&a.price_change_percent));
+    let gainers: Vec<Ticker> = markets.iter().take(count).cloned().collect();
+    let losers: Vec<Ticker> = markets.iter().rev().take(count).cloned().collect();
+
+    markets.sort_by(|a, b| b.quote_volume.total_cmp(&a.quote_volume));
+    let volume: Vec<Ticker> = markets.into_iter().take(count).collect();
+
+    Ok((gainers, losers, volume))
+}
+
+pub async fn klines(symbol: &str, interval: &str, limit: u16) -> Result<Vec<Candle>, CryptoError> {
+    klines_from(symbol, interval, limit, None).await
+}
+
+async fn klines_from(
+    symbol: &str,
+    interval: &str,
+    limit: u16,
+    start_time: Option<u64>,
+) -> Result<Vec<Candle>, CryptoError> {
+    log::info!("Fetching {interval} klines for {symbol}");
+    let symbol = symbol.to_string();
+    let interval = interval.to_string();
+
+    let summaries =
+        market_call(move |market| market.get_klines(symbol, interval, limit, start_time, None))
+            .await?;
+
+    let KlineSummaries::AllKlineSummaries(summaries) = summaries;
+
+    let candles = summaries
+        .into_iter()
+        .map(|kline| Candle {
+            open_time: kline.open_time,
+            open: kline.open.parse().unwrap_or_default(),
+            high: kline.high.parse().unwrap_or_default(),
+            low: kline.low.parse().unwrap_or_default(),
+            close: kline.close.parse().unwrap_or_default(),
+            volume: kline.volume.parse().unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(candles)
+}
+
+// Every daily candle since the start time, Binance returns at most KLINE_PAGE per request
+pub async fn daily_klines_since(symbol: &str, start_time: i64) -> Result<Vec<Candle>, CryptoError> {
+    let mut candles: Vec<Candle> = Vec::new();
+    let mut start_time = start_time.max(0) as u64;
+
+    loop {
+        let page = klines_from(symbol, "1d", KLINE_PAGE, Some(start_time)).await?;
+        let full = page.len() == KLINE_PAGE as usize;
+
+        let Some(last) = page.last() else {
+            break;
+        };
+        start_time = last.open_time as u64 + 1;
+        candles.extend(page);
+
+        if !full {
+            break;
+        }
+    }
+
+    Ok(candles)
+}
+
+// Latest prices for the requested pairs, served from the ticker cache with one request for the rest
+pub async fn prices(symbols: &[String]) -> Result<HashMap<String, f64>, CryptoError> {
+    let mut prices: HashMap<String, f64> = symbols
+        .iter()
+        .filter_map(|symbol| {
+            cached_ticker(symbol).map(|ticker| (symbol.clone(), ticker.last_price))
+        })
+        .collect();
+
+    if prices.len() == symbols.len() {
+        return Ok(prices);
+    }
+
+    for (symbol, price) in all_prices().await? {
+        if symbols.contains(&symbol) && !prices.contains_key(&symbol) {
+            prices.insert(symbol, price);
+        }
+    }
+
+    Ok(prices)
+}
+
+// Last price of every pair on Binance keyed by symbol
+pub async fn all_prices() -> Result<HashMap<String, f64>, CryptoError> {
+    let Prices::AllPrices(all_prices) = market_call(|market| market.get_all_prices()).await?;
+
+    Ok(all_prices
+        .into_iter()
+        .map(|price| (price.symbol, price.price))
+        .collect())
+}
+
+// Opening price of the pair `minutes` ago, minute klines are used while a page of them covers the window.
+// It only changes once the next candle opens, so it is cached until then
+pub async fn price_minutes_ago(symbol: &str, minutes: u32) -> Result<f64, CryptoError> {
+    if minutes > MAX_WINDOW_MINUTES {
+        return Err(CryptoError::NotEnoughHistory(symbol.to_string()));
+    }
+
+    let (interval, step, count) = if minutes < u32::from(KLINE_PAGE) {
+        ("1m", 60_000, minutes)
+    } else {
+        ("1h", 3_600_000, minutes / 60)
+    };
+
+    let now = Utc::now().timestamp_millis();
+    let start = now - now % step - i64::from(count) * step;
+    let key = (symbol.to_string(), minutes);
+
+    let cached = WINDOW_STARTS
+        .read()
+        .expect("window cache lock poisoned")
+        .get(&key)
+        .copied();
+    if let Some((_, price)) = cached.filter(|(open_time, _)| *open_time == start) {
+        return Ok(price);
+    }
+
+    let candles = klines_from(symbol, interval, 1, Some(start as u64)).await?;
+    let price = candles
+        .first()
+        .map(|candle| candle.open)
+        .ok_or_else(|| CryptoError::InvalidSymbol(symbol.to_string()))?;
+
+    WINDOW_STARTS
+        .write()
+        .expect("window cache lock poisoned")
+        .insert(key, (start, price));
+
+    Ok(price)
+}
+
+// Best `levels` bids and asks together with the latest trades
+pub async fn market_depth(symbol: &str, levels: usize) -> Result<MarketDepth, CryptoError> {
+    log::info!("Fetching order book for {symbol}");
+    let limit = DEPTH_LIMITS
+        .iter()
+        .copied()
+        .find(|limit| *limit as usize >= levels)
+        .unwrap_or(DEPTH_LIMITS[DEPTH_LIMITS.len() - 1]);
+
+    let book_symbol = symbol.to_string();
+    let book = market_call(move |market| market.get_custom_depth(book_symbol, limit)).await?;
+
+    let trades_symbol = symbol.to_string();
+    let trades = market_call(move |market| {
+        market.get_agg_trades(trades_symbol, None, None, None, RECENT_TRADES)
+    })
+    .await?;
+
+    Ok(MarketDepth {
+        bids: book
+            .bids
+            .into_iter()
+            .take(levels)
+            .map(|bid| BookLevel {
+                price: bid.price,
+                quantity: bid.qty,
+            })
+            .collect(),
+        asks: book
+            .asks
+            .into_iter()
+            .take(levels)
+            .map(|ask| BookLevel {
+                price: ask.price,
+                quantity: ask.qty,
+            })
+            .collect(),
+        trades: trades
+            .into_iter()
+            .rev()
+            .map(|trade| RecentTrade {
+                time: trade.time,
+                price: trade.price,
+                quantity: trade.qty,
+                buyer_maker: trade.maker,
+            })
+            .collect(),
+    })
 }
 
-pub async fn handle_input(message: &String) {
-    
-}
\ No newline at end of file
+// Binance prices arrive as f64, this keeps the shortest decimal that round-trips
+pub fn to_decimal(value: f64) -> Option<Decimal> {
+    Decimal::from_f64(value).map(|value| value.normalize())
+}
+
+// Large prices get cents, small caps keep enough digits to be useful
+pub fn format_price(price: f64) -> String {
+    if price.abs() >= 1.0 {
+        format!("{:.2}", price)
+    } else {
+        let formatted = format!("{:.8}", price);
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+pub fn format_ticker(ticker: &Ticker) -> String {
+    let mut message = String::new();
+    message.push_str(&format!("{}\n", ticker.symbol));
+    message.push_str(&format!("Price: {}\n", format_price(ticker.last_price)));
+    message.push_str(&format!(
+        "24h Change: {:+.2}% ({}{})\n",
+        ticker.price_change_percent,
+        if ticker.price_change >= 0.0 { "+" } else { "" },
+        format_price(ticker.price_change)
+    ));
+    message.push_str(&format!("24h High: {}\n", format_price(ticker.high_price)));
+    message.push_str(&format!("24h Low: {}\n", format_price(ticker.low_price)));
+    message.push_str(&format!("24h Volume: {:.2}\n", ticker.volume));
+    message
+}
+
+pub fn compact_number(value: f64) -> String {
+    match value {
+        v if v >= 1_000_000_000.0 => format!("{:.2}B", v / 1_000_000_000.0),
+        v if v >= 1_000_000.0 => format!("{:.2}M", v / 1_000_000.0),
+        v if v >= 1_000.0 => format!("{:.2}K", v / 1_000.0),
+        v => format!("{:.2}", v),
+    }
+}
+
+// Monospace rows of pair, price and 24h change, meant to be wrapped in <pre>
+pub fn format_ticker_table(tickers: &[Ticker], show_volume: bool) -> String {
+    let mut table = if show_volume {
+        format!(
+            "{:<12}{:>14}{:>9}{:>10}\n",
+            "PAIR", "PRICE", "24H", "VOLUME"
+        )
+    } else {
+        format!("{:<12}{:>14}{:>9}\n", "PAIR", "PRICE", "24H")
+    };
+
+    for ticker in tickers {
+        table.push_str(&format!(
+            "{:<12}{:>14}{:>+8.2}%",
+            ticker.symbol,
+            format_price(ticker.last_price),
+            ticker.price_change_percent
+        ));
+        if show_volume {
+            table.push_str(&format!("{:>10}", compact_number(ticker.quote_volume)));
+        }
+        table.push('\n');
+    }
+
+    table
+}
+
+pub fn format_quantity(quantity: f64) -> String {
+    if quantity >= 1_000.0 {
+        compact_number(quantity)
+    } else {
+        format!("{:.4}", quantity)
+    }
+}
+
+fn format_levels(levels: &[BookLevel]) -> Vec<String> {
+    let mut cumulative = 0.0;
+
+    levels
+        .iter()
+        .map(|level| {
+            cumulative += level.quantity;
+            format!(
+                "{:>12}{:>10}{:>10}",
+                format_price(level.price),
+                format_quantity(level.quantity),
+                format_quantity(cumulative)
+            )
+        })
+        .collect()
+}
+
+// Asks above the spread, bids below it, then the last trades, meant to be wrapped in <pre>
+pub fn format_market_depth(depth: &MarketDepth) -> String {
+    let mut table = String::new();
+
+    if let (Some(bid), Some(ask)) = (depth.bids.first(), depth.asks.first()) {
+        let spread = ask.price - bid.price;
+        let mid = (ask.price + bid.price) / 2.0;
+        table.push_str(&format!("Mid: {}\n", format_price(mid)));
+        table.push_str(&format!(
+            "Spread: {} ({:.3}%)\n\n",
+            format_price(spread),
+            spread / mid * 100.0
+        ));
+    }
+
+    table.push_str(&format!("{:>12}{:>10}{:>10}\n", "ASK", "SIZE", "TOTAL"));
+    for row in format_levels(&depth.asks).iter().rev() {
+        table.push_str(&format!("{row}\n"));
+    }
+    table.push_str(&format!("{}\n", "-".repeat(32)));
+    for row in format_levels(&depth.bids) {
+        table.push_str(&format!("{row}\n"));
+    }
+    table.push_str(&format!("{:>12}{:>10}{:>10}\n\n", "BID", "SIZE", "TOTAL"));
+
+    table.push_str(&format!(
+        "{:<9}{:>12}{:>10} {}\n",
+        "TIME", "PRICE", "SIZE", "SIDE"
+    ));
+    for trade in &depth.trades {
+        let time = DateTime::<Utc>::from_timestamp_millis(trade.time as i64)
+            .map(|time| time.format("%H:%M:%S").to_string())
+            .unwrap_or_default();
+        let side = if trade.buyer_maker { "SELL" } else { "BUY" };
+        table.push_str(&format!(
+            "{:<9}{:>12}{:>10} {}\n",
+            time,
+            format_price(trade.price),
+            format_quantity(trade.quantity),
+            side
+        ));
+    }
+
+    table
+}
+
+pub async fn price_summary(input: &str) -> Result<String, CryptoError> {
+    let symbol = normalize_symbol(input)?;
+    let ticker = ticker(&symbol).await?;
+    Ok(format_ticker(&ticker))
+}
+
+pub async fn price_chart(symbol: &str, interval: &str) -> Result<Vec<u8>, CryptoError> {
+    if !CHART_INTERVALS.contains(&interval) {
+        return Err(CryptoError::InvalidInterval(interval.to_string()));
+    }
+
+    let candles = klines(symbol, interval, CHART_CANDLES).await?;
+    let symbol = symbol.to_string();
+    let interval = interval.to_string();
+
+    let png = task::spawn_blocking(move || {
+        chart_service::render_candlestick(&symbol, &interval, &candles, None)
+    })
+    .await??;
+
+    Ok(png)
+}
@@ -1,32 +1,540 @@
-use chatgpt::prelude::*;
-use std::env;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use std::{collections::HashMap, sync::Mutex};
+use teloxide::types::{ChatId, UserId};
+use tokio::sync::watch;
 
-#[tokio::main]
-pub async fn main() -> Result<()> {
-    let client = ChatGPT::new(env::var("GPT_API_KEY").expect("expect Open AI Key to be set"))?;
+use crate::{
+    models::{
+        assets::AiError,
+        conversation::{ChatRole, ChatTurn, ContextPolicy, Conversation},
+        llm::{Completion, Provider, WireMessage},
+        moderation::{Direction, Verdict},
+    },
+    service::{
+        llm_service, moderation_service, persona_service, settings_service, tool_service,
+        usage_service,
+    },
+    utils::{
+        storage,
+        tokens::{estimate_tokens, estimate_turn, estimate_turns},
+    },
+};
 
-    let resonse = client.send_message("Describe the best programing language!").await?;
+const CONVERSATIONS_FILE: &str = "conversations.json";
+// Rounds of tool calls allowed before the model has to answer with what it has
+const MAX_TOOL_ROUNDS: usize = 3;
+const SUMMARY_PROMPT: &str = "Summarize the conversation below for your own future reference. \
+    Keep names, facts, preferences, decisions and open questions, write at most 150 words and add nothing new.";
+const DIGEST_PROMPT: &str = "Summarize the chat messages below for someone who missed them. Start with a short overview, \
+    then list the key decisions, open questions and action items if there are any, and finish with the links that were shared. \
+    Be concise, use plain text without markdown and write in the language most of the messages use.";
 
-    println!("response is {}", resonse.message().content);
+lazy_static! {
+    static ref CONVERSATIONS: Mutex<HashMap<i64, Conversation>> =
+        Mutex::new(storage::load(CONVERSATIONS_FILE));
+}
+
+fn conversation(chat_id: ChatId) -> Conversation {
+    CONVERSATIONS
+        .lock()
+        .expect("conversations lock poisoned")
+        .get(&chat_id.0)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn summary_turn(summary: &str) -> ChatTurn {
+    ChatTurn::new(
+        ChatRole::System,
+        &format!("Summary of the earlier conversation: {summary}"),
+    )
+}
+
+// Number of oldest turns to drop so the rest fits the budget, never splitting a question from its answer
+fn excess_turns(turns: &[ChatTurn], budget: usize) -> usize {
+    let mut total = estimate_turns(turns);
+    let mut cut = 0;
+
+    while cut < turns.len() && (total > budget || turns[cut].role != ChatRole::User) {
+        total -= estimate_turn(&turns[cut]);
+        cut += 1;
+    }
+
+    cut
+}
 
+fn record(chat_id: ChatId, turns: &[ChatTurn]) -> Result<(), AiError> {
+    let mut conversations = CONVERSATIONS.lock().expect("conversations lock poisoned");
+    let conversation = conversations.entry(chat_id.0).or_default();
+
+    conversation.turns.extend_from_slice(turns);
+    conversation.updated_at = Utc::now().timestamp();
+
+    storage::save(CONVERSATIONS_FILE, &*conversations)?;
     Ok(())
 }
 
-pub async fn gpt_quick_reply(message: &String) -> Result<String> {
-    println!("this is {message}");
+// The emptied conversation stays behind with its generation bumped, for compactions still running
+pub fn reset(chat_id: ChatId) -> Result<bool, AiError> {
+    let mut conversations = CONVERSATIONS.lock().expect("conversations lock poisoned");
+    let Some(conversation) = conversations.get_mut(&chat_id.0) else {
+        return Ok(false);
+    };
 
-    let client = ChatGPT::new(env::var("GPT_API_KEY").expect("expect Open AI Key to be set"))?;
+    let removed = !conversation.turns.is_empty() || conversation.summary.is_some();
+    *conversation = Conversation {
+        generation: conversation.generation + 1,
+        ..Conversation::default()
+    };
+    storage::save(CONVERSATIONS_FILE, &*conversations)?;
 
-    let resonse = client.send_message(message).await?;
+    Ok(removed)
+}
 
-    Ok(resonse.message().content.to_string())
+// Usage is billed to whoever's message caused the request, a failure to store it shouldn't lose the reply
+fn bill(user_id: UserId, chat_id: ChatId, model: &str, completion: &Completion) {
+    if let Err(err) = usage_service::record(user_id, chat_id, model, completion.usage) {
+        log::error!(
+            "Failed to record AI usage of {} in {}: {}",
+            user_id,
+            chat_id,
+            err
+        );
+    }
 }
 
-pub async fn maintain_conversation(message: &String) -> Result<String> {
-    let client = ChatGPT::new(env::var("GPT_API_KEY").expect("expect Open AI Key to be set"))?;
+async fn summarize(
+    provider: &Provider,
+    user_id: UserId,
+    chat_id: ChatId,
+    previous: Option<&str>,
+    turns: &[ChatTurn],
+) -> Result<String, AiError> {
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(&format!("Earlier summary: {previous}\n\n"));
+    }
+    for turn in turns {
+        let speaker = match turn.role {
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+            ChatRole::System => "System",
+        };
+        transcript.push_str(&format!("{speaker}: {}\n", turn.content));
+    }
+
+    let messages = [
+        WireMessage::from(&ChatTurn::new(ChatRole::System, SUMMARY_PROMPT)),
+        WireMessage::from(&ChatTurn::new(ChatRole::User, &transcript)),
+    ];
+    let model = llm_service::chat_model(chat_id);
+    let completion = llm_service::complete(provider, &model, &messages).await?;
+    bill(user_id, chat_id, &model, &completion);
+
+    Ok(completion.content.trim().to_string())
+}
+
+// Redacted text replaces the original, blocked text ends the request with a message for the user
+async fn moderate(
+    user_id: UserId,
+    chat_id: ChatId,
+    text: &str,
+    direction: Direction,
+) -> Result<String, AiError> {
+    let verdict = moderation_service::check(text, direction).await;
+    moderation_service::report(chat_id, user_id, direction, &verdict, text).await;
+
+    match verdict {
+        Verdict::Allowed => Ok(text.to_string()),
+        Verdict::Redacted { text, .. } => Ok(text),
+        Verdict::Blocked { .. } => Err(AiError::Moderated(match direction {
+            Direction::Input => "Sorry, I can't help with that message.".to_string(),
+            Direction::Output => "The answer was withheld by the moderation filter.".to_string(),
+        })),
+    }
+}
 
-    let mut conversation = client.new_conversation();
-    let response = conversation.send_message(message).await?;
+// The reply thread may hold messages that never went to the AI, they pass the input check too.
+// Blocked ones are left out rather than failing the request of whoever replied to them
+async fn moderate_thread(chat_id: ChatId, thread: &[ChatTurn]) -> Vec<ChatTurn> {
+    let mut checked = Vec::with_capacity(thread.len());
 
-    Ok(response.message().content.clone())
-}
\ No newline at end of file
+    for turn in thread {
+        match moderation_service::check(&turn.content, Direction::Input).await {
+            Verdict::Allowed => checked.push(turn.clone()),
+            Verdict::Redacted { text, .. } => checked.push(ChatTurn::new(turn.role, &text)),
+            Verdict::Blocked { reasons } => {
+                log::info!(
+                    "Left a thread message out of the AI request in {}: {}",
+                    chat_id,
+                    reasons.join(", ")
+                );
+            }
+        }
+    }
+
+    checked
+}
+
+// One-off summary of chat messages, it doesn't touch the chat's AI conversation
+pub async fn summarize_messages(
+    provider: &Provider,
+    user_id: UserId,
+    chat_id: ChatId,
+    transcript: &str,
+) -> Result<String, AiError> {
+    usage_service::check_quota(user_id, chat_id)?;
+    let transcript = moderate(user_id, chat_id, transcript, Direction::Input).await?;
+
+    let messages = [
+        WireMessage::from(&ChatTurn::new(ChatRole::System, DIGEST_PROMPT)),
+        WireMessage::from(&ChatTurn::new(ChatRole::User, &transcript)),
+    ];
+    let model = llm_service::chat_model(chat_id);
+    let completion = llm_service::complete(provider, &model, &messages).await?;
+    bill(user_id, chat_id, &model, &completion);
+
+    moderate(
+        user_id,
+        chat_id,
+        completion.content.trim(),
+        Direction::Output,
+    )
+    .await
+}
+
+// Answers a question about an image with a vision model. Only the question and answer are remembered,
+// so follow-ups in AI mode know what was discussed without paying for the image again
+pub async fn ask_about_image(
+    provider: &Provider,
+    user_id: UserId,
+    chat_id: ChatId,
+    question: &str,
+    image_url: String,
+) -> Result<String, AiError> {
+    usage_service::check_quota(user_id, chat_id)?;
+    let question = moderate(user_id, chat_id, question, Direction::Input).await?;
+
+    let messages: Vec<WireMessage> = persona_service::system_prompt(chat_id)
+        .map(|prompt| WireMessage::from(&ChatTurn::new(ChatRole::System, &prompt)))
+        .into_iter()
+        .chain(std::iter::once(WireMessage::user_with_image(
+            &question, image_url,
+        )))
+        .collect();
+    let model = llm_service::vision_model(chat_id);
+    let completion = llm_service::complete(provider, &model, &messages).await?;
+    bill(user_id, chat_id, &model, &completion);
+
+    let answer = moderate(
+        user_id,
+        chat_id,
+        completion.content.trim(),
+        Direction::Output,
+    )
+    .await?;
+    record(
+        chat_id,
+        &[
+            ChatTurn::new(ChatRole::User, &format!("(sent a photo) {question}")),
+            ChatTurn::new(ChatRole::Assistant, &answer),
+        ],
+    )?;
+    if let Err(err) = compact(provider, user_id, chat_id).await {
+        log::error!("Failed to compact the conversation in {}: {}", chat_id, err);
+    }
+
+    Ok(answer)
+}
+
+// Keeps the history within the chat's budget, summarizing down to half of it so this doesn't run every turn
+async fn compact(provider: &Provider, user_id: UserId, chat_id: ChatId) -> Result<(), AiError> {
+    let settings = settings_service::get(chat_id).context;
+    let conversation = conversation(chat_id);
+
+    if estimate_turns(&conversation.turns) <= settings.budget {
+        return Ok(());
+    }
+
+    let (cut, summary) = match settings.policy {
+        ContextPolicy::Truncate => (
+            excess_turns(&conversation.turns, settings.budget),
+            conversation.summary,
+        ),
+        ContextPolicy::Summarize => {
+            let cut = excess_turns(&conversation.turns, settings.budget / 2);
+            match summarize(
+                provider,
+                user_id,
+                chat_id,
+                conversation.summary.as_deref(),
+                &conversation.turns[..cut],
+            )
+            .await
+            {
+                Ok(summary) => (cut, Some(summary)),
+                Err(err) => {
+                    log::warn!(
+                        "Summarizing chat {} failed, truncating instead: {}",
+                        chat_id,
+                        err
+                    );
+                    (
+                        excess_turns(&conversation.turns, settings.budget),
+                        conversation.summary,
+                    )
+                }
+            }
+        }
+    };
+
+    let mut conversations = CONVERSATIONS.lock().expect("conversations lock poisoned");
+    // Turns appended meanwhile leave the prefix as it was, a reset or another compaction doesn't
+    match conversations.get_mut(&chat_id.0) {
+        Some(current) if current.generation == conversation.generation => {
+            current.turns.drain(..cut);
+            current.summary = summary;
+            current.generation += 1;
+            storage::save(CONVERSATIONS_FILE, &*conversations)?;
+        }
+        _ => {
+            log::info!(
+                "Chat {} changed while it was compacted, leaving it as it is",
+                chat_id
+            );
+            return Ok(());
+        }
+    }
+
+    log::info!("Compacted {} turns of chat {}", cut, chat_id);
+    Ok(())
+}
+
+// Persona, summary and as much recent history as the budget allows, then the reply thread the question
+// belongs to without what the history already holds, followed by the question
+fn build_messages(chat_id: ChatId, thread: &[ChatTurn], question: &ChatTurn) -> Vec<WireMessage> {
+    let budget = settings_service::get(chat_id).context.budget;
+    let conversation = conversation(chat_id);
+    let recent = &conversation.turns[excess_turns(&conversation.turns, budget)..];
+    let thread = thread
+        .iter()
+        .filter(|turn| !recent.iter().any(|seen| seen.content == turn.content));
+
+    persona_service::system_prompt(chat_id)
+        .map(|prompt| ChatTurn::new(ChatRole::System, &prompt))
+        .into_iter()
+        .chain(conversation.summary.as_deref().map(summary_turn))
+        .chain(recent.iter().cloned())
+        .chain(thread.cloned())
+        .chain(std::iter::once(question.clone()))
+        .map(|turn| WireMessage::from(&turn))
+        .collect()
+}
+
+// Estimated token usage of everything sent along with a new message
+pub fn describe_context(chat_id: ChatId) -> String {
+    let settings = settings_service::get(chat_id).context;
+    let conversation = conversation(chat_id);
+
+    let persona = persona_service::system_prompt(chat_id)
+        .map(|prompt| estimate_tokens(&prompt))
+        .unwrap_or_default();
+    let summary = conversation
+        .summary
+        .as_deref()
+        .map(|summary| estimate_turn(&summary_turn(summary)))
+        .unwrap_or_default();
+    let history = estimate_turns(&conversation.turns);
+    let used = summary + history;
+
+    let policy = match settings.policy {
+        ContextPolicy::Truncate => "truncate, the oldest messages are forgotten",
+        ContextPolicy::Summarize => "summarize, the oldest messages are folded into a summary",
+    };
+
+    let mut message = String::from("AI context for this chat (estimated tokens):\n\n");
+    message.push_str(&format!("Policy: {policy}\n"));
+    message.push_str(&format!("Budget: {}\n", settings.budget));
+    message.push_str(&format!("Persona: {persona}\n"));
+    message.push_str(&format!("Summary: {summary}\n"));
+    message.push_str(&format!(
+        "History: {history} in {} messages\n",
+        conversation.turns.len()
+    ));
+    message.push_str(&format!(
+        "Usage: {used} of {} ({}%)\n\n",
+        settings.budget,
+        used * 100 / settings.budget.max(1)
+    ));
+    message.push_str("Change it with /context summarize, /context truncate or /context budget 4000. /reset clears everything.");
+    message
+}
+// Sends the persona, summary, recent history and the new message, streaming the reply so far into `partial`.
+// The model may call the bot's tools first, their results are fed back before it answers.
+// Both turns are kept only once the model has finished answering, every request counts towards the user's quota.
+// The question, the reply thread and the answer pass moderation on their way
+pub async fn maintain_conversation(
+    provider: &Provider,
+    user_id: UserId,
+    chat_id: ChatId,
+    message: &str,
+    thread: &[ChatTurn],
+    partial: watch::Sender<String>,
+) -> Result<String, AiError> {
+    usage_service::check_quota(user_id, chat_id)?;
+    let message = moderate(user_id, chat_id, message, Direction::Input).await?;
+
+    let question = ChatTurn::new(ChatRole::User, &message);
+    let thread = moderate_thread(chat_id, thread).await;
+
+    let mut messages = build_messages(chat_id, &thread, &question);
+
+    let model = llm_service::chat_model(chat_id);
+    let tools = tool_service::definitions();
+    let mut reply = String::new();
+
+    for round in 0..=MAX_TOOL_ROUNDS {
+        // The last round offers no tools so the model has to answer
+        let offered = if round < MAX_TOOL_ROUNDS {
+            tools.as_slice()
+        } else {
+            &[]
+        };
+        let completion =
+            llm_service::stream(provider, &model, &messages, offered, &partial).await?;
+        bill(user_id, chat_id, &model, &completion);
+
+        // Text written before tool calls stays part of the answer, the next round continues after it
+        reply.push_str(&completion.content);
+        if completion.tool_calls.is_empty() {
+            break;
+        }
+        if !completion.content.is_empty() {
+            reply.push_str("\n\n");
+            partial.send_modify(|text| text.push_str("\n\n"));
+        }
+
+        let calls = completion.tool_calls.clone();
+        messages.push(WireMessage::assistant_calls(
+            &completion.content,
+            completion.tool_calls,
+        ));
+        for call in &calls {
+            let result =
+                tool_service::call(chat_id, &call.function.name, &call.function.arguments).await;
+            messages.push(WireMessage::tool_result(&call.id, result));
+        }
+    }
+
+    let reply = moderate(user_id, chat_id, &reply, Direction::Output).await?;
+    record(
+        chat_id,
+        &[question, ChatTurn::new(ChatRole::Assistant, &reply)],
+    )?;
+
+    if let Err(err) = compact(provider, user_id, chat_id).await {
+        log::error!("Failed to compact the conversation in {}: {}", chat_id, err);
+    }
+
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, sync::Once};
+
+    static STORE: Once = Once::new();
+
+    // The services keep their state in json files, each test run writes its own to a scratch directory
+    fn scratch_store() {
+        STORE.call_once(|| {
+            let dir = env::temp_dir().join(format!("crunchy_bot_tests_{}", std::process::id()));
+            env::set_var("STORE_DIR", dir);
+        });
+    }
+
+    fn turns(roles: &[ChatRole]) -> Vec<ChatTurn> {
+        roles
+            .iter()
+            .map(|role| ChatTurn::new(*role, "four words per turn"))
+            .collect()
+    }
+
+    #[test]
+    fn excess_turns_keeps_what_fits() {
+        let turns = turns(&[
+            ChatRole::User,
+            ChatRole::Assistant,
+            ChatRole::User,
+            ChatRole::Assistant,
+        ]);
+        let total = estimate_turns(&turns);
+
+        assert_eq!(excess_turns(&turns, total), 0);
+        assert_eq!(excess_turns(&turns, total - 1), 2);
+        assert_eq!(excess_turns(&turns, 0), turns.len());
+    }
+
+    #[test]
+    fn excess_turns_never_starts_with_an_answer() {
+        let turns = turns(&[
+            ChatRole::User,
+            ChatRole::Assistant,
+            ChatRole::Assistant,
+            ChatRole::User,
+        ]);
+        let last = estimate_turn(&turns[3]);
+
+        // Dropping only the first turn would leave two answers without their question
+        assert_eq!(excess_turns(&turns, estimate_turns(&turns) - 1), 3);
+        assert_eq!(excess_turns(&turns[1..], last), 2);
+    }
+
+    #[tokio::test]
+    async fn mock_conversation_streams_and_is_remembered() {
+        scratch_store();
+        let (user_id, chat_id) = (UserId(900_001), ChatId(-900_001));
+        let (partial, watcher) = watch::channel(String::new());
+        let provider = Provider::Mock { tool: None };
+
+        let reply =
+            maintain_conversation(&provider, user_id, chat_id, "good morning", &[], partial)
+                .await
+                .unwrap();
+
+        assert_eq!(reply, "Mock reply to: good morning");
+        assert_eq!(*watcher.borrow(), reply);
+        let turns = conversation(chat_id).turns;
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[1].content, reply);
+        assert_eq!(usage_service::user_usage(user_id).today.requests, 1);
+    }
+
+    #[tokio::test]
+    async fn tool_rounds_are_cut_off() {
+        scratch_store();
+        let (user_id, chat_id) = (UserId(900_002), ChatId(-900_002));
+        let (partial, watcher) = watch::channel(String::new());
+        // The mock asks for a tool every time one is offered, an unknown one so nothing leaves the test
+        let provider = Provider::Mock {
+            tool: Some("missing_tool".to_string()),
+        };
+
+        let reply =
+            maintain_conversation(&provider, user_id, chat_id, "loop forever", &[], partial)
+                .await
+                .unwrap();
+
+        // Each round's text is kept and the next one continues after it
+        assert_eq!(
+            reply,
+            "Checking missing_tool.\n\n".repeat(MAX_TOOL_ROUNDS) + "Mock reply to: loop forever"
+        );
+        assert_eq!(*watcher.borrow(), reply);
+        let usage = usage_service::user_usage(user_id).today;
+        assert_eq!(usage.requests, MAX_TOOL_ROUNDS as u64 + 1);
+        assert!(usage.prompt_tokens > 0 && usage.completion_tokens > 0);
+    }
+}
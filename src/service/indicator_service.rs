@@ -0,0 +1,238 @@
+use tokio::task;
+
+use crate::{
+    models::{
+        assets::CryptoError,
+        crypto::{Analysis, Candle, Overlays},
+    },
+    service::{
+        chart_service,
+        crypto_service::{self, format_price, CHART_INTERVALS},
+    },
+    utils::indicators,
+};
+
+// Enough history for the slow SMA to settle, only the most recent candles are drawn
+const HISTORY_CANDLES: u16 = 250;
+const CHART_CANDLES: usize = 90;
+const RSI_PERIOD: usize = 14;
+const MACD_FAST: usize = 12;
+const MACD_SLOW: usize = 26;
+const MACD_SIGNAL: usize = 9;
+const SMA_FAST: usize = 20;
+const SMA_SLOW: usize = 50;
+const EMA_FAST: usize = 9;
+const EMA_SLOW: usize = 21;
+const BOLLINGER_PERIOD: usize = 20;
+const BOLLINGER_DEVIATIONS: f64 = 2.0;
+// Crosses older than this many candles are not worth mentioning
+const CROSS_LOOKBACK: usize = 5;
+
+fn last(series: &[Option<f64>]) -> Option<f64> {
+    series.last().copied().flatten()
+}
+
+fn candles_ago(cross: Option<(usize, bool)>, len: usize) -> Option<(usize, bool)> {
+    cross
+        .map(|(index, upward)| (len - 1 - index, upward))
+        .filter(|(ago, _)| *ago < CROSS_LOOKBACK)
+}
+
+fn compute(symbol: &str, interval: &str, candles: &[Candle]) -> Option<(Analysis, Overlays)> {
+    let closes: Vec<f64> = candles.iter().map(|candle| candle.close).collect();
+
+    let rsi = indicators::rsi(&closes, RSI_PERIOD);
+    let (macd, macd_signal, macd_histogram) =
+        indicators::macd(&closes, MACD_FAST, MACD_SLOW, MACD_SIGNAL);
+    let sma_fast = indicators::sma(&closes, SMA_FAST);
+    let sma_slow = indicators::sma(&closes, SMA_SLOW);
+    let ema_fast = indicators::ema(&closes, EMA_FAST);
+    let ema_slow = indicators::ema(&closes, EMA_SLOW);
+    let (bollinger_upper, bollinger_middle, bollinger_lower) =
+        indicators::bollinger(&closes, BOLLINGER_PERIOD, BOLLINGER_DEVIATIONS);
+
+    let analysis = Analysis {
+        symbol: symbol.to_string(),
+        interval: interval.to_string(),
+        close: *closes.last()?,
+        rsi: last(&rsi)?,
+        macd: last(&macd)?,
+        macd_signal: last(&macd_signal)?,
+        macd_histogram: last(&macd_histogram)?,
+        macd_cross: candles_ago(indicators::last_cross(&macd, &macd_signal), closes.len()),
+        sma_fast: last(&sma_fast)?,
+        sma_slow: last(&sma_slow)?,
+        sma_cross: candles_ago(indicators::last_cross(&sma_fast, &sma_slow), closes.len()),
+        ema_fast: last(&ema_fast)?,
+        ema_slow: last(&ema_slow)?,
+        bollinger_upper: last(&bollinger_upper)?,
+        bollinger_middle: last(&bollinger_middle)?,
+        bollinger_lower: last(&bollinger_lower)?,
+    };
+
+    let overlays = Overlays {
+        sma_fast,
+        sma_slow,
+        bollinger_upper,
+        bollinger_lower,
+    };
+
+    Some((analysis, overlays))
+}
+
+pub async fn analyze(
+    symbol: &str,
+    interval: &str,
+) -> Result<(Analysis, Overlays, Vec<Candle>), CryptoError> {
+    if !CHART_INTERVALS.contains(&interval) {
+        return Err(CryptoError::InvalidInterval(interval.to_string()));
+    }
+
+    let candles = crypto_service::klines(symbol, interval, HISTORY_CANDLES).await?;
+    let (analysis, overlays) = compute(symbol, interval, &candles)
+        .ok_or_else(|| CryptoError::NotEnoughHistory(symbol.to_string()))?;
+
+    Ok((analysis, overlays, candles))
+}
+
+fn cross_note(cross: Option<(usize, bool)>, up: &str, down: &str) -> String {
+    match cross {
+        Some((0, upward)) => format!(", {} on the last candle", if upward { up } else { down }),
+        Some((ago, upward)) => format!(", {} {ago} candles ago", if upward { up } else { down }),
+        None => String::new(),
+    }
+}
+
+// MACD values hover around zero, so they are shown signed rather than as prices
+fn format_oscillator(value: f64) -> String {
+    if value.abs() >= 1.0 {
+        format!("{value:+.2}")
+    } else {
+        format!("{value:+.6}")
+    }
+}
+
+fn trend(bullish: bool) -> &'static str {
+    if bullish {
+        "bullish"
+    } else {
+        "bearish"
+    }
+}
+
+// Each indicator votes bullish, bearish or neutral and the votes decide the overall bias
+pub fn format_analysis(analysis: &Analysis) -> String {
+    let mut score = 0;
+
+    let rsi_note = if analysis.rsi >= 70.0 {
+        score -= 1;
+        "overbought"
+    } else if analysis.rsi <= 30.0 {
+        score += 1;
+        "oversold"
+    } else {
+        "neutral"
+    };
+
+    let macd_bullish = analysis.macd > analysis.macd_signal;
+    let sma_bullish = analysis.sma_fast > analysis.sma_slow;
+    let ema_bullish = analysis.ema_fast > analysis.ema_slow;
+    for bullish in [macd_bullish, sma_bullish, ema_bullish] {
+        score += if bullish { 1 } else { -1 };
+    }
+
+    let band_width = analysis.bollinger_upper - analysis.bollinger_lower;
+    let percent_b = if band_width > 0.0 {
+        (analysis.close - analysis.bollinger_lower) / band_width * 100.0
+    } else {
+        50.0
+    };
+    let band_note = if analysis.close > analysis.bollinger_upper {
+        score -= 1;
+        "price above the upper band"
+    } else if analysis.close < analysis.bollinger_lower {
+        score += 1;
+        "price below the lower band"
+    } else if percent_b >= 50.0 {
+        "price in the upper half"
+    } else {
+        "price in the lower half"
+    };
+
+    let overall = match score {
+        score if score >= 2 => "bullish",
+        score if score <= -2 => "bearish",
+        _ => "neutral",
+    };
+
+    let mut message = format!(
+        "{} {} technical analysis\n",
+        analysis.symbol, analysis.interval
+    );
+    message.push_str(&format!("Price: {}\n\n", format_price(analysis.close)));
+    message.push_str(&format!(
+        "RSI({RSI_PERIOD}): {:.1} - {rsi_note}\n",
+        analysis.rsi
+    ));
+    message.push_str(&format!(
+        "MACD({MACD_FAST},{MACD_SLOW},{MACD_SIGNAL}): {} / signal {} / histogram {} - {}{}\n",
+        format_oscillator(analysis.macd),
+        format_oscillator(analysis.macd_signal),
+        format_oscillator(analysis.macd_histogram),
+        trend(macd_bullish),
+        cross_note(
+            analysis.macd_cross,
+            "crossed above the signal",
+            "crossed below the signal"
+        )
+    ));
+    message.push_str(&format!(
+        "SMA {SMA_FAST}/{SMA_SLOW}: {} / {} - {}{}\n",
+        format_price(analysis.sma_fast),
+        format_price(analysis.sma_slow),
+        trend(sma_bullish),
+        cross_note(analysis.sma_cross, "golden cross", "death cross")
+    ));
+    message.push_str(&format!(
+        "EMA {EMA_FAST}/{EMA_SLOW}: {} / {} - {}\n",
+        format_price(analysis.ema_fast),
+        format_price(analysis.ema_slow),
+        trend(ema_bullish)
+    ));
+    message.push_str(&format!(
+        "Bollinger({BOLLINGER_PERIOD},{BOLLINGER_DEVIATIONS}): {} - {} - {}, {band_note} (%B {:.0}%)\n\n",
+        format_price(analysis.bollinger_lower),
+        format_price(analysis.bollinger_middle),
+        format_price(analysis.bollinger_upper),
+        percent_b
+    ));
+    message.push_str(&format!("Overall: {overall}\n"));
+    message.push_str("This is not financial advice.");
+
+    message
+}
+
+// Candlestick chart of the recent candles with the SMAs and Bollinger bands drawn over them
+pub async fn analysis_chart(
+    candles: Vec<Candle>,
+    overlays: Overlays,
+    analysis: &Analysis,
+) -> Result<Vec<u8>, CryptoError> {
+    let start = candles.len().saturating_sub(CHART_CANDLES);
+    let candles = candles[start..].to_vec();
+    let overlays = Overlays {
+        sma_fast: overlays.sma_fast[start..].to_vec(),
+        sma_slow: overlays.sma_slow[start..].to_vec(),
+        bollinger_upper: overlays.bollinger_upper[start..].to_vec(),
+        bollinger_lower: overlays.bollinger_lower[start..].to_vec(),
+    };
+    let symbol = analysis.symbol.clone();
+    let interval = analysis.interval.clone();
+
+    let png = task::spawn_blocking(move || {
+        chart_service::render_candlestick(&symbol, &interval, &candles, Some(&overlays))
+    })
+    .await??;
+
+    Ok(png)
+}
@@ -0,0 +1,493 @@
+use futures::StreamExt;
+use reqwest::RequestBuilder;
+use serde_json::Value;
+use std::env;
+use teloxide::types::ChatId;
+use tokio::sync::watch;
+
+use crate::{
+    models::{
+        assets::AiError,
+        llm::{
+            Completion, CompletionChunk, CompletionRequest, CompletionResponse, FunctionCall,
+            ModelList, Provider, StreamOptions, TokenUsage, ToolCall, WireMessage,
+        },
+    },
+    service::settings_service,
+    utils::tokens::{estimate_messages, estimate_tokens},
+};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+const MOCK_MODEL: &str = "mock";
+// Calls the model may make in one answer, pieces with a higher index are dropped
+const MAX_TOOL_CALLS: usize = 16;
+
+// OpenAI compatible by default, LLM_BASE_URL points it at another server and LLM_PROVIDER=mock needs none
+pub fn provider() -> Provider {
+    match env::var("LLM_PROVIDER")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "mock" => Provider::Mock {
+            tool: env::var("LLM_MOCK_TOOL").ok(),
+        },
+        _ => Provider::OpenAiCompatible {
+            base_url: env::var("LLM_BASE_URL")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+            api_key: env::var("LLM_API_KEY")
+                .or_else(|_| env::var("GPT_API_KEY"))
+                .ok(),
+        },
+    }
+}
+
+pub fn default_model() -> String {
+    env::var("LLM_MODEL")
+        .or_else(|_| env::var("GPT_MODEL"))
+        .unwrap_or_else(|_| match provider() {
+            Provider::Mock { .. } => MOCK_MODEL.to_string(),
+            Provider::OpenAiCompatible { .. } => DEFAULT_MODEL.to_string(),
+        })
+}
+
+pub fn chat_model(chat_id: ChatId) -> String {
+    settings_service::get(chat_id)
+        .model
+        .unwrap_or_else(default_model)
+}
+
+// VISION_MODEL when the chat model can't see images
+pub fn vision_model(chat_id: ChatId) -> String {
+    env::var("VISION_MODEL").unwrap_or_else(|_| chat_model(chat_id))
+}
+
+// Local servers usually need no key, OpenAI itself always does. Azure takes its key in LLM_AUTH_HEADER=api-key
+// instead of a bearer token, LLM_BASE_URL is then the deployment's URL and LLM_API_VERSION its api-version
+pub fn authorize(
+    request: RequestBuilder,
+    base_url: &str,
+    api_key: Option<&str>,
+) -> Result<RequestBuilder, AiError> {
+    let request = match env::var("LLM_API_VERSION") {
+        Ok(version) => request.query(&[("api-version", version)]),
+        Err(_) => request,
+    };
+
+    match (api_key, env::var("LLM_AUTH_HEADER")) {
+        (Some(key), Ok(header)) => Ok(request.header(header, key)),
+        (Some(key), Err(_)) => Ok(request.bearer_auth(key)),
+        (None, _) if base_url == DEFAULT_BASE_URL => Err(AiError::MissingKey),
+        (None, _) => Ok(request),
+    }
+}
+
+pub async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, AiError> {
+    let status = response.status();
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(AiError::Api {
+            status: status.as_u16(),
+            message,
+        });
+    }
+
+    Ok(response)
+}
+
+async fn send(
+    base_url: &str,
+    api_key: Option<&str>,
+    model: &str,
+    messages: &[WireMessage],
+    tools: &[Value],
+    stream: bool,
+) -> Result<reqwest::Response, AiError> {
+    let request = CompletionRequest {
+        model,
+        messages,
+        stream,
+        stream_options: stream.then_some(StreamOptions {
+            include_usage: true,
+        }),
+        tools,
+    };
+
+    let builder = reqwest::Client::new().post(format!("{base_url}/chat/completions"));
+    let response = authorize(builder, base_url, api_key)?
+        .json(&request)
+        .send()
+        .await?;
+
+    check_status(response).await
+}
+
+// Models group admins may pick from, without LLM_MODELS only bot admins can change the model
+pub fn allowed_models() -> Option<Vec<String>> {
+    env::var("LLM_MODELS").ok().map(|models| {
+        models
+            .split(',')
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+// LLM_MODELS when set, otherwise whatever the server lists
+pub async fn available_models() -> Result<Vec<String>, AiError> {
+    if let Some(models) = allowed_models() {
+        return Ok(models);
+    }
+
+    let (base_url, api_key) = match provider() {
+        Provider::Mock { .. } => return Ok(vec![MOCK_MODEL.to_string()]),
+        Provider::OpenAiCompatible { base_url, api_key } => (base_url, api_key),
+    };
+
+    let builder = reqwest::Client::new().get(format!("{base_url}/models"));
+    let response = authorize(builder, &base_url, api_key.as_deref())?
+        .send()
+        .await?;
+    let list: ModelList = check_status(response).await?.json().await?;
+
+    let mut models: Vec<String> = list.data.into_iter().map(|model| model.id).collect();
+    models.sort();
+    Ok(models)
+}
+
+// Echoes the question back word by word, so conversations, streaming and billing can be tried offline
+fn mock_completion(
+    tool: Option<&str>,
+    messages: &[WireMessage],
+    tools: &[Value],
+    partial: Option<&watch::Sender<String>>,
+) -> Completion {
+    if let Some(tool) = tool.filter(|_| !tools.is_empty()) {
+        let round = messages
+            .iter()
+            .filter(|message| !message.tool_calls.is_empty())
+            .count();
+        let mut completion = Completion {
+            content: format!("Checking {tool}."),
+            tool_calls: vec![ToolCall {
+                id: format!("mock-call-{round}"),
+                kind: "function".to_string(),
+                function: FunctionCall {
+                    name: tool.to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }],
+            ..Completion::default()
+        };
+        if let Some(partial) = partial {
+            partial.send_modify(|text| text.push_str(&completion.content));
+        }
+        completion.usage = usage_or_estimate(None, messages, &completion);
+        return completion;
+    }
+
+    let question = messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(WireMessage::text)
+        .unwrap_or_default();
+
+    let mut completion = Completion::default();
+    for word in format!("Mock reply to: {question}").split_inclusive(' ') {
+        completion.content.push_str(word);
+        if let Some(partial) = partial {
+            partial.send_modify(|text| text.push_str(word));
+        }
+    }
+
+    completion.usage = usage_or_estimate(None, messages, &completion);
+    completion
+}
+
+// Services that don't report usage are billed by the estimator instead
+fn usage_or_estimate(
+    usage: Option<TokenUsage>,
+    messages: &[WireMessage],
+    completion: &Completion,
+) -> TokenUsage {
+    usage.unwrap_or_else(|| {
+        let calls: usize = completion
+            .tool_calls
+            .iter()
+            .map(|call| estimate_tokens(&call.function.arguments))
+            .sum();
+        TokenUsage {
+            prompt_tokens: estimate_messages(messages) as u64,
+            completion_tokens: (estimate_tokens(&completion.content) + calls) as u64,
+        }
+    })
+}
+
+// Waits for the whole answer, used where nobody is watching the reply being written
+pub async fn complete(
+    provider: &Provider,
+    model: &str,
+    messages: &[WireMessage],
+) -> Result<Completion, AiError> {
+    let (base_url, api_key) = match provider {
+        Provider::Mock { tool } => {
+            return Ok(mock_completion(tool.as_deref(), messages, &[], None))
+        }
+        Provider::OpenAiCompatible { base_url, api_key } => (base_url, api_key),
+    };
+
+    let response: CompletionResponse =
+        send(base_url, api_key.as_deref(), model, messages, &[], false)
+            .await?
+            .json()
+            .await?;
+
+    let mut completion = Completion {
+        content: response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.text())
+            .unwrap_or_default(),
+        ..Completion::default()
+    };
+    completion.usage = usage_or_estimate(response.usage, messages, &completion);
+
+    Ok(completion)
+}
+
+fn apply_chunk(
+    chunk: CompletionChunk,
+    completion: &mut Completion,
+    usage: &mut Option<TokenUsage>,
+    partial: &watch::Sender<String>,
+) {
+    if chunk.usage.is_some() {
+        *usage = chunk.usage;
+    }
+
+    for choice in chunk.choices {
+        if let Some(delta) = choice.delta.content {
+            completion.content.push_str(&delta);
+            // Nobody listening is fine, the full reply is still returned
+            partial.send_modify(|text| text.push_str(&delta));
+        }
+
+        for piece in choice.delta.tool_calls {
+            if piece.index >= MAX_TOOL_CALLS {
+                log::warn!("Skipping tool call with index {}", piece.index);
+                continue;
+            }
+            if completion.tool_calls.len() <= piece.index {
+                completion
+                    .tool_calls
+                    .resize_with(piece.index + 1, ToolCall::default);
+            }
+            let call = &mut completion.tool_calls[piece.index];
+
+            if let Some(id) = piece.id {
+                call.id = id;
+                call.kind = "function".to_string();
+            }
+            if let Some(function) = piece.function {
+                call.function
+                    .name
+                    .push_str(function.name.as_deref().unwrap_or_default());
+                call.function
+                    .arguments
+                    .push_str(function.arguments.as_deref().unwrap_or_default());
+            }
+        }
+    }
+}
+
+// Streams one completion over server-sent events, new text is appended to what `partial` already holds
+pub async fn stream(
+    provider: &Provider,
+    model: &str,
+    messages: &[WireMessage],
+    tools: &[Value],
+    partial: &watch::Sender<String>,
+) -> Result<Completion, AiError> {
+    let (base_url, api_key) = match provider {
+        Provider::Mock { tool } => {
+            return Ok(mock_completion(
+                tool.as_deref(),
+                messages,
+                tools,
+                Some(partial),
+            ))
+        }
+        Provider::OpenAiCompatible { base_url, api_key } => (base_url, api_key),
+    };
+
+    let mut body = send(base_url, api_key.as_deref(), model, messages, tools, true)
+        .await?
+        .bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut completion = Completion::default();
+    let mut usage = None;
+
+    'events: while let Some(bytes) = body.next().await {
+        buffer.extend_from_slice(&bytes?);
+
+        // Events may be split across network chunks, so only complete lines are parsed
+        while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+
+            if data == "[DONE]" {
+                break 'events;
+            }
+
+            match serde_json::from_str::<CompletionChunk>(data) {
+                Ok(chunk) => apply_chunk(chunk, &mut completion, &mut usage, partial),
+                Err(err) => log::warn!("Skipping unreadable completion chunk: {}", err),
+            }
+        }
+    }
+
+    completion.usage = usage_or_estimate(usage, messages, &completion);
+    Ok(completion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::conversation::{ChatRole, ChatTurn};
+
+    fn question(text: &str) -> Vec<WireMessage> {
+        vec![
+            WireMessage::from(&ChatTurn::new(ChatRole::System, "Be brief.")),
+            WireMessage::from(&ChatTurn::new(ChatRole::User, text)),
+        ]
+    }
+
+    #[test]
+    fn tool_call_pieces_beyond_the_limit_are_dropped() {
+        let (partial, _watcher) = watch::channel(String::new());
+        let mut completion = Completion::default();
+        let mut usage = None;
+        let chunk: CompletionChunk = serde_json::from_str(
+            r#"{"choices": [{"delta": {"tool_calls": [
+                {"index": 1, "id": "call_1", "function": {"name": "get_crypto_movers", "arguments": "{}"}},
+                {"index": 4000000000, "id": "call_2", "function": {"name": "get_crypto_movers"}}
+            ]}}]}"#,
+        )
+        .unwrap();
+
+        apply_chunk(chunk, &mut completion, &mut usage, &partial);
+
+        assert_eq!(completion.tool_calls.len(), 2);
+        assert_eq!(completion.tool_calls[1].id, "call_1");
+        assert_eq!(completion.tool_calls[1].function.name, "get_crypto_movers");
+    }
+
+    #[tokio::test]
+    async fn mock_stream_publishes_partial_text() {
+        let (partial, watcher) = watch::channel(String::new());
+        let messages = question("how are you");
+
+        let completion = stream(
+            &Provider::Mock { tool: None },
+            MOCK_MODEL,
+            &messages,
+            &[],
+            &partial,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(completion.content, "Mock reply to: how are you");
+        assert_eq!(*watcher.borrow(), completion.content);
+        assert!(completion.tool_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mock_usage_is_estimated() {
+        let messages = question("hello");
+
+        let completion = complete(&Provider::Mock { tool: None }, MOCK_MODEL, &messages)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            completion.usage.prompt_tokens,
+            estimate_messages(&messages) as u64
+        );
+        assert_eq!(
+            completion.usage.completion_tokens,
+            estimate_tokens(&completion.content) as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn mock_calls_its_tool_only_when_offered() {
+        let (partial, _watcher) = watch::channel(String::new());
+        let provider = Provider::Mock {
+            tool: Some("get_price".to_string()),
+        };
+        let messages = question("price of btc");
+        let tools = [serde_json::json!({ "type": "function" })];
+
+        let called = stream(&provider, MOCK_MODEL, &messages, &tools, &partial)
+            .await
+            .unwrap();
+        let answered = stream(&provider, MOCK_MODEL, &messages, &[], &partial)
+            .await
+            .unwrap();
+
+        assert_eq!(called.tool_calls.len(), 1);
+        assert_eq!(called.tool_calls[0].function.name, "get_price");
+        assert!(answered.tool_calls.is_empty());
+        assert_eq!(answered.content, "Mock reply to: price of btc");
+    }
+
+    #[test]
+    fn reported_usage_wins_over_the_estimate() {
+        let reported = TokenUsage {
+            prompt_tokens: 7,
+            completion_tokens: 3,
+        };
+
+        let usage = usage_or_estimate(Some(reported), &question("hello"), &Completion::default());
+
+        assert_eq!(usage.prompt_tokens, 7);
+        assert_eq!(usage.completion_tokens, 3);
+    }
+
+    #[test]
+    fn chunks_assemble_tool_calls() {
+        let (partial, _watcher) = watch::channel(String::new());
+        let mut completion = Completion::default();
+        let mut usage = None;
+        let chunks = [
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"get_price","arguments":"{\"sym"}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"bol\":\"BTCUSDT\"}"}}]}}]}"#,
+            r#"{"choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5}}"#,
+        ];
+
+        for chunk in chunks {
+            apply_chunk(
+                serde_json::from_str(chunk).unwrap(),
+                &mut completion,
+                &mut usage,
+                &partial,
+            );
+        }
+
+        assert_eq!(completion.tool_calls[0].id, "call_1");
+        assert_eq!(
+            completion.tool_calls[0].function.arguments,
+            r#"{"symbol":"BTCUSDT"}"#
+        );
+        assert_eq!(usage.map(|usage| usage.prompt_tokens), Some(10));
+    }
+}
@@ -1,5 +1,23 @@
-pub mod telegram_service;
+pub mod alert_service;
+pub mod backtest_service;
+pub mod chart_service;
+pub mod conversion_service;
+pub mod crypto_service;
 pub mod gpt_service;
-pub mod soccer_service;
+pub mod indicator_service;
+pub mod llm_service;
+pub mod moderation_service;
 pub mod movie_service;
-pub mod crypto_service;
+pub mod paper_service;
+pub mod persona_service;
+pub mod portfolio_service;
+pub mod settings_service;
+pub mod soccer_service;
+pub mod speech_service;
+pub mod summary_service;
+pub mod telegram_service;
+pub mod thread_service;
+pub mod tool_service;
+pub mod usage_service;
+pub mod vision_service;
+pub mod watchlist_service;
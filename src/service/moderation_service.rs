@@ -0,0 +1,290 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::env;
+use teloxide::types::{ChatId, UserId};
+
+use crate::{
+    models::{
+        assets::AiError,
+        llm::Provider,
+        moderation::{
+            Direction, ModerationAction, ModerationRequest, ModerationResponse, ModerationRule,
+            Verdict,
+        },
+    },
+    service::llm_service,
+    utils::{data::MODERATION_RULES, environment, storage},
+};
+
+const MODERATION_RULES_FILE: &str = "moderation_rules.json";
+const DEFAULT_MODERATION_MODEL: &str = "omni-moderation-latest";
+const REDACTED: &str = "[redacted]";
+const EXCERPT_CHARS: usize = 300;
+// Partials leave out the newest words, so a block rule spanning up to this many words has matched before any of
+// its words show. Longer phrases can show their first words while the answer streams
+const HELD_BACK_WORDS: usize = 8;
+
+struct CompiledRule {
+    name: String,
+    regex: Regex,
+    action: ModerationAction,
+}
+
+lazy_static! {
+    // A moderation_rules.json in the store replaces the built-in rules
+    static ref RULES: Vec<CompiledRule> = {
+        let custom: Vec<ModerationRule> = storage::load(MODERATION_RULES_FILE);
+        let rules = if custom.is_empty() { MODERATION_RULES.clone() } else { custom };
+
+        rules
+            .into_iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Some(CompiledRule {
+                    name: rule.name,
+                    regex,
+                    action: rule.action,
+                }),
+                Err(err) => {
+                    log::error!("Skipping moderation rule {}: {}", rule.name, err);
+                    None
+                }
+            })
+            .collect()
+    };
+}
+
+// MODERATION=off turns it off, rules (the default) only applies the local rules and
+// provider also asks the provider's moderation endpoint
+fn mode() -> String {
+    env::var("MODERATION")
+        .unwrap_or_else(|_| "rules".to_string())
+        .to_lowercase()
+}
+
+pub fn apply_rules(text: &str) -> Verdict {
+    let mut cleaned = text.to_string();
+    let mut reasons = Vec::new();
+
+    for rule in RULES.iter() {
+        if !rule.regex.is_match(&cleaned) {
+            continue;
+        }
+
+        match rule.action {
+            ModerationAction::Block => {
+                return Verdict::Blocked {
+                    reasons: vec![rule.name.clone()],
+                }
+            }
+            ModerationAction::Redact => {
+                cleaned = rule.regex.replace_all(&cleaned, REDACTED).into_owned();
+                reasons.push(rule.name.clone());
+            }
+        }
+    }
+
+    if reasons.is_empty() {
+        Verdict::Allowed
+    } else {
+        Verdict::Redacted {
+            text: cleaned,
+            reasons,
+        }
+    }
+}
+
+// Every rule match removed, blocking ones included, so incident reports don't repeat what they report
+fn sanitized(text: &str) -> String {
+    RULES.iter().fold(text.to_string(), |text, rule| {
+        rule.regex.replace_all(&text, REDACTED).into_owned()
+    })
+}
+
+// Names of the categories the provider flagged, empty when the text is fine
+async fn provider_flags(text: &str) -> Result<Vec<String>, AiError> {
+    let (base_url, api_key) = match llm_service::provider() {
+        Provider::Mock { .. } => return Ok(Vec::new()),
+        Provider::OpenAiCompatible { base_url, api_key } => (base_url, api_key),
+    };
+    let base_url = env::var("MODERATION_BASE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or(base_url);
+    let model =
+        env::var("MODERATION_MODEL").unwrap_or_else(|_| DEFAULT_MODERATION_MODEL.to_string());
+
+    let builder = reqwest::Client::new().post(format!("{base_url}/moderations"));
+    let response = llm_service::authorize(builder, &base_url, api_key.as_deref())?
+        .json(&ModerationRequest {
+            model: &model,
+            input: text,
+        })
+        .send()
+        .await?;
+    let moderation: ModerationResponse = llm_service::check_status(response).await?.json().await?;
+
+    let mut flags = Vec::new();
+    for result in moderation
+        .results
+        .into_iter()
+        .filter(|result| result.flagged)
+    {
+        let mut categories: Vec<String> = result
+            .categories
+            .into_iter()
+            .filter(|(_, flagged)| *flagged)
+            .map(|(category, _)| category)
+            .collect();
+        if categories.is_empty() {
+            categories.push("flagged".to_string());
+        }
+        flags.extend(categories);
+    }
+    flags.sort();
+    flags.dedup();
+
+    Ok(flags)
+}
+
+pub async fn check(text: &str, direction: Direction) -> Verdict {
+    let mode = mode();
+    if mode == "off" {
+        return Verdict::Allowed;
+    }
+
+    let verdict = apply_rules(text);
+    if mode != "provider" || matches!(verdict, Verdict::Blocked { .. }) {
+        return verdict;
+    }
+
+    let checked = match &verdict {
+        Verdict::Redacted { text, .. } => text.as_str(),
+        _ => text,
+    };
+    match provider_flags(checked).await {
+        Ok(flags) if !flags.is_empty() => Verdict::Blocked { reasons: flags },
+        Ok(_) => verdict,
+        // An unreachable moderation endpoint shouldn't take the AI down with it
+        Err(err) => {
+            log::warn!("Moderation endpoint failed for {}: {}", direction, err);
+            verdict
+        }
+    }
+}
+
+// The provider's check needs the whole answer, so with it nothing is shown while the answer streams
+pub fn streams_partials() -> bool {
+    mode() != "provider"
+}
+
+// The last word may still grow into something a rule matches, so it is held back until whitespace follows it
+fn complete_words(text: &str) -> &str {
+    &text[..text.rfind(char::is_whitespace).unwrap_or(0)]
+}
+
+// The part of a partial that may be shown, without the newest words and any digits before them that a card
+// number could continue
+fn settled(text: &str) -> &str {
+    let mut end = complete_words(text).len();
+    for _ in 1..HELD_BACK_WORDS {
+        end = text[..end]
+            .trim_end()
+            .rfind(char::is_whitespace)
+            .unwrap_or(0);
+    }
+    text[..end].trim_end_matches(|c: char| c.is_ascii_digit() || c.is_whitespace() || c == '-')
+}
+
+// Streamed answers are shown before the full check can run, so each partial gets the local rules.
+// A block anywhere in the words so far hides the whole partial
+pub fn screen_partial(text: &str) -> String {
+    if mode() == "off" {
+        return text.to_string();
+    }
+
+    if matches!(apply_rules(complete_words(text)), Verdict::Blocked { .. }) {
+        return "…".to_string();
+    }
+
+    let text = settled(text);
+    match apply_rules(text) {
+        Verdict::Allowed => text.to_string(),
+        Verdict::Redacted { text, .. } => text,
+        Verdict::Blocked { .. } => "…".to_string(),
+    }
+}
+
+// Incidents go to the service chat with the offending parts removed
+pub async fn report(
+    chat_id: ChatId,
+    user_id: UserId,
+    direction: Direction,
+    verdict: &Verdict,
+    text: &str,
+) {
+    let (outcome, reasons) = match verdict {
+        Verdict::Allowed => return,
+        Verdict::Redacted { reasons, .. } => ("Redacted", reasons),
+        Verdict::Blocked { reasons } => ("Blocked", reasons),
+    };
+
+    let excerpt: String = sanitized(text).chars().take(EXCERPT_CHARS).collect();
+    let message = format!(
+        "Moderation: {outcome} AI {direction} in chat {chat_id} from user {user_id} ({})\n\n{excerpt}",
+        reasons.join(", ")
+    );
+
+    log::warn!(
+        "{outcome} AI {direction} in {chat_id} from {user_id}: {}",
+        reasons.join(", ")
+    );
+    if let Err(err) = environment::log(&message).await {
+        log::warn!("Failed to report a moderation incident: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILLER: &str = "one two three four five six seven eight";
+
+    #[test]
+    fn partial_holds_back_the_newest_words() {
+        assert_eq!(
+            screen_partial(&format!("The answer is {FILLER}")),
+            "The answer is"
+        );
+    }
+
+    #[test]
+    fn partial_holds_back_a_number_in_progress() {
+        let shown = screen_partial(&format!("Your card is 4111 1111 11 {FILLER}"));
+
+        assert_eq!(shown, "Your card is");
+    }
+
+    #[test]
+    fn partial_redacts_a_finished_card_number() {
+        let shown = screen_partial(&format!("Your card is 4111 1111 1111 1111 and {FILLER}"));
+
+        assert!(!shown.contains("1111"));
+        assert!(shown.contains(REDACTED));
+    }
+
+    #[test]
+    fn streamed_block_phrase_never_shows() {
+        let answer = "Sure, here is how to make a bomb at home, step by step, with what you have.";
+
+        // Every prefix the stream could deliver, one character at a time
+        for end in (1..=answer.len()).filter(|end| answer.is_char_boundary(*end)) {
+            let shown = screen_partial(&answer[..end]);
+
+            assert!(
+                !shown.contains("how"),
+                "{shown:?} shown for {:?}",
+                &answer[..end]
+            );
+        }
+        assert_eq!(screen_partial(answer), "…");
+    }
+}
@@ -1,66 +1,155 @@
-use reqwest::{self, Error as ReqwestError, Response};
+use reqwest;
 use serde_json::Value;
-use std::{env, fmt::format};
+use std::env;
 
-use crate::models::movie::TrendingMovieApiResponse;
+use crate::models::{
+    movie::{TrendingMovieApiResponse, TrendingMovieResult},
+    settings::ChatSettings,
+};
 
 const MOVIE_BASE_URL: &str = "https://api.themoviedb.org";
 
-async fn fetch_movies(endpoint: &str) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let movie_api_token = env::var("MOVIE_ACCESS_TOKEN")
-        .map_err(|_| "MOVIE_ACCESS_TOKEN must be set")?;
+// Query TMDB and drop adult titles unless the chat allows them
+async fn fetch_movie_results(
+    endpoint: &str,
+    params: &[(&str, &str)],
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<TrendingMovieResult>, Box<dyn std::error::Error + Send + Sync>> {
+    let movie_api_token =
+        env::var("MOVIE_ACCESS_TOKEN").map_err(|_| "MOVIE_ACCESS_TOKEN must be set")?;
 
     let client = reqwest::Client::new();
     let url = format!("{}/3{}", MOVIE_BASE_URL, endpoint);
+    let include_adult = settings.allow_adult.to_string();
 
     let response = client
         .get(url)
         .header("Authorization", format!("Bearer {}", movie_api_token))
+        .query(&[
+            ("include_adult", include_adult.as_str()),
+            ("language", language),
+        ])
+        .query(params)
         .send()
         .await?;
 
     let body: Value = response.json().await?;
     let response_object: TrendingMovieApiResponse = serde_json::from_value(body)?;
 
-    let mut response: Vec<String> = Vec::new();
-    
-    for (index, movie) in response_object.results.iter().enumerate() {
-        let mut message = String::new();
-        message.push_str(&format!("Movie {}:\n", index + 1));
-        message.push_str(&format!("Title: {}\n", movie.title));
-        message.push_str(&format!("Original Title: {}\n", movie.original_title));
-        message.push_str(&format!("Overview: {}\n", movie.overview));
-        message.push_str(&format!("Adult: {}\n", movie.adult));
-        message.push_str(&format!("Original Language: {}\n", movie.original_language));
-        message.push_str(&format!("Release Date: {}\n", movie.release_date));
-        message.push_str("--------------------\n");
-
-        response.push(message);
+    let movies = response_object
+        .results
+        .into_iter()
+        .filter(|movie| settings.allow_adult || !movie.adult)
+        .collect();
+
+    Ok(movies)
+}
+
+// TMDB leaves fields empty when there is no translation for the requested language
+pub fn display_title(movie: &TrendingMovieResult) -> &str {
+    if movie.title.trim().is_empty() {
+        &movie.original_title
+    } else {
+        &movie.title
     }
+}
+
+pub fn format_movie(index: usize, movie: &TrendingMovieResult) -> String {
+    let overview = if movie.overview.trim().is_empty() {
+        "No overview available in this language."
+    } else {
+        &movie.overview
+    };
+
+    let mut message = String::new();
+    message.push_str(&format!("Movie {}:\n", index + 1));
+    message.push_str(&format!("Title: {}\n", display_title(movie)));
+    message.push_str(&format!("Original Title: {}\n", movie.original_title));
+    message.push_str(&format!("Overview: {}\n", overview));
+    message.push_str(&format!("Adult: {}\n", movie.adult));
+    message.push_str(&format!("Original Language: {}\n", movie.original_language));
+    message.push_str(&format!("Release Date: {}\n", movie.release_date));
+    message.push_str("--------------------\n");
+    message
+}
+
+async fn fetch_movies(
+    endpoint: &str,
+    params: &[(&str, &str)],
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let movies = fetch_movie_results(endpoint, params, settings, language).await?;
+
+    let response = movies
+        .iter()
+        .enumerate()
+        .map(|(index, movie)| format_movie(index, movie))
+        .collect();
 
     Ok(response)
 }
 
-pub async fn trending_movie() -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn trending_movie(
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Fetching trending movie");
-    let movies = fetch_movies("/trending/movie/day?language=en-US").await?;
+    let movies = fetch_movies("/trending/movie/day", &[], settings, language).await?;
     Ok(movies)
 }
 
-pub async fn popular_movie() -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn popular_movie(
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Fetching popular movie");
-    let movies = fetch_movies("/movie/popular").await?;
+    let movies = fetch_movies("/movie/popular", &[], settings, language).await?;
     Ok(movies)
 }
 
-pub async fn get_movies_in_theatres() -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn get_movies_in_theatres(
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Get a list of movies that are currently in theatres.");
-    let movies = fetch_movies("/movie/now_playing").await?;
+    let movies = fetch_movies("/movie/now_playing", &[], settings, language).await?;
     Ok(movies)
 }
 
-pub async fn upcoming_movie() -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn upcoming_movie(
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Get a list of movies that are being released soon..");
-    let movies = fetch_movies("/movie/upcoming").await?;
+    let movies = fetch_movies("/movie/upcoming", &[], settings, language).await?;
     Ok(movies)
-}
\ No newline at end of file
+}
+
+pub async fn trending_movie_results(
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<TrendingMovieResult>, Box<dyn std::error::Error + Send + Sync>> {
+    log::info!("Fetching trending movie results");
+    fetch_movie_results("/trending/movie/day", &[], settings, language).await
+}
+
+pub async fn search_movie_results(
+    query: &str,
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<TrendingMovieResult>, Box<dyn std::error::Error + Send + Sync>> {
+    log::info!("Searching movies for {query}");
+    fetch_movie_results("/search/movie", &[("query", query)], settings, language).await
+}
+
+pub async fn search_movie(
+    query: &str,
+    settings: &ChatSettings,
+    language: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    log::info!("Searching movies for {query}");
+    let movies = fetch_movies("/search/movie", &[("query", query)], settings, language).await?;
+    Ok(movies)
+}
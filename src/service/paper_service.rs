@@ -0,0 +1,558 @@
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr, sync::Mutex, time::Duration};
+use teloxide::{prelude::*, types::ChatId};
+
+use crate::{
+    models::{
+        assets::PaperError,
+        paper::{PaperAccount, PaperBook, PaperFill, PaperOrder},
+        portfolio::TradeSide,
+    },
+    service::crypto_service::{self, to_decimal},
+    utils::storage,
+};
+
+const PAPER_FILE: &str = "paper_trading.json";
+const QUOTE_ASSET: &str = "USDT";
+const STARTING_BALANCE: Decimal = Decimal::from_parts(10_000, 0, 0, false, 0);
+const MATCH_INTERVAL: Duration = Duration::from_secs(10);
+const HISTORY_LIMIT: usize = 15;
+// Keeps quantity * price far from Decimal's limits, no real order comes near either bound
+const MAX_QUANTITY: Decimal = Decimal::from_parts(1_000_000_000, 0, 0, false, 0);
+const MAX_PRICE: Decimal = Decimal::from_parts(1_000_000_000, 0, 0, false, 0);
+
+lazy_static! {
+    static ref PAPER: Mutex<PaperBook> = Mutex::new(storage::load(PAPER_FILE));
+}
+
+fn new_account(name: &str) -> PaperAccount {
+    PaperAccount {
+        name: name.to_string(),
+        balance: STARTING_BALANCE,
+        holdings: HashMap::new(),
+        open_orders: Vec::new(),
+        history: Vec::new(),
+    }
+}
+
+fn account_mut<'a>(book: &'a mut PaperBook, user_id: UserId, name: &str) -> &'a mut PaperAccount {
+    let account = book
+        .accounts
+        .entry(user_id.0)
+        .or_insert_with(|| new_account(name));
+    account.name = name.to_string();
+    account
+}
+
+fn remember_member(book: &mut PaperBook, chat_id: ChatId, user_id: UserId) {
+    if chat_id.is_user() {
+        return;
+    }
+
+    let members = book.chat_members.entry(chat_id.0).or_default();
+    if !members.contains(&user_id.0) {
+        members.push(user_id.0);
+    }
+}
+
+fn parse_decimal(value: &str) -> Option<Decimal> {
+    Decimal::from_str(&value.replace(',', ""))
+        .ok()
+        .filter(|number| number.is_sign_positive() && !number.is_zero())
+}
+
+// "<pair> <quantity> [limit price]"
+fn parse_order(args: &str) -> Result<(String, Decimal, Option<Decimal>), PaperError> {
+    let invalid = || PaperError::InvalidOrder(args.trim().to_string());
+    let words: Vec<&str> = args.split_whitespace().collect();
+
+    let (symbol, quantity, limit) = match words.as_slice() {
+        [symbol, quantity] => (symbol, quantity, None),
+        [symbol, quantity, limit] => (
+            symbol,
+            quantity,
+            Some(
+                parse_decimal(limit)
+                    .filter(|limit| *limit <= MAX_PRICE)
+                    .ok_or_else(invalid)?,
+            ),
+        ),
+        _ => return Err(invalid()),
+    };
+
+    let symbol = crypto_service::normalize_symbol(symbol)?;
+    if !symbol.ends_with(QUOTE_ASSET) {
+        return Err(PaperError::UnsupportedPair(symbol));
+    }
+
+    let quantity = parse_decimal(quantity)
+        .filter(|quantity| *quantity <= MAX_QUANTITY)
+        .ok_or_else(invalid)?;
+
+    Ok((symbol, quantity, limit))
+}
+
+fn notional(quantity: Decimal, price: Decimal) -> Option<Decimal> {
+    quantity.checked_mul(price)
+}
+
+fn fill(
+    order_id: u64,
+    side: TradeSide,
+    symbol: &str,
+    quantity: Decimal,
+    price: Decimal,
+    limit: bool,
+) -> PaperFill {
+    PaperFill {
+        order_id,
+        side,
+        symbol: symbol.to_string(),
+        quantity,
+        price,
+        limit,
+        time: Utc::now().timestamp(),
+    }
+}
+
+// `total` is the fill's quantity * price, checked by the caller
+fn execute(account: &mut PaperAccount, fill: PaperFill, total: Decimal) {
+    let quantity = fill.quantity;
+    let symbol = fill.symbol.as_str();
+    let holding = account.holdings.entry(symbol.to_string()).or_default();
+
+    // Limit orders already moved their funds or coins into the reservation
+    match (fill.side, fill.limit) {
+        (TradeSide::Buy, false) => {
+            account.balance -= total;
+            *holding += quantity;
+        }
+        (TradeSide::Buy, true) => *holding += quantity,
+        (TradeSide::Sell, false) => {
+            *holding -= quantity;
+            account.balance += total;
+        }
+        (TradeSide::Sell, true) => account.balance += total,
+    }
+
+    if holding.is_zero() {
+        account.holdings.remove(symbol);
+    }
+
+    account.history.push(fill);
+}
+
+fn check_funds(
+    account: &PaperAccount,
+    side: TradeSide,
+    symbol: &str,
+    quantity: Decimal,
+    total: Decimal,
+) -> Result<(), PaperError> {
+    match side {
+        TradeSide::Buy if total > account.balance => Err(PaperError::InsufficientBalance {
+            needed: total.round_dp(2).to_string(),
+            available: account.balance.round_dp(2).to_string(),
+        }),
+        TradeSide::Sell => {
+            let held = account.holdings.get(symbol).copied().unwrap_or_default();
+            if quantity > held {
+                Err(PaperError::InsufficientHoldings {
+                    symbol: symbol.to_string(),
+                    needed: quantity.normalize().to_string(),
+                    available: held.normalize().to_string(),
+                })
+            } else {
+                Ok(())
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+// Market orders fill right away, limit orders fill now if marketable or rest until the matcher fills them
+pub async fn place_order(
+    user_id: UserId,
+    name: &str,
+    chat_id: ChatId,
+    side: TradeSide,
+    args: &str,
+) -> Result<String, PaperError> {
+    let (symbol, quantity, limit) = parse_order(args)?;
+
+    let ticker = crypto_service::ticker(&symbol).await?;
+    let market_price = to_decimal(ticker.last_price)
+        .ok_or_else(|| PaperError::InvalidOrder(args.trim().to_string()))?;
+
+    let marketable = match (side, limit) {
+        (_, None) => true,
+        (TradeSide::Buy, Some(limit)) => market_price <= limit,
+        (TradeSide::Sell, Some(limit)) => market_price >= limit,
+    };
+
+    let price = if marketable {
+        market_price
+    } else {
+        limit.unwrap_or(market_price)
+    };
+    let total = notional(quantity, price)
+        .ok_or_else(|| PaperError::InvalidOrder(args.trim().to_string()))?;
+
+    let mut book = PAPER.lock().expect("paper lock poisoned");
+    // Funds are checked before anything changes, so a rejected order leaves the book untouched
+    match book.accounts.get(&user_id.0) {
+        Some(account) => check_funds(account, side, &symbol, quantity, total)?,
+        None => check_funds(&new_account(name), side, &symbol, quantity, total)?,
+    }
+
+    book.next_order_id += 1;
+    let order_id = book.next_order_id;
+    remember_member(&mut book, chat_id, user_id);
+    let account = account_mut(&mut book, user_id, name);
+
+    let reply = if marketable {
+        execute(
+            account,
+            fill(order_id, side, &symbol, quantity, market_price, false),
+            total,
+        );
+        format!(
+            "Paper order #{order_id} filled: {side} {} {symbol} at {}. Balance: {} {QUOTE_ASSET}",
+            quantity.normalize(),
+            market_price,
+            account.balance.round_dp(2)
+        )
+    } else {
+        let limit_price = price;
+
+        match side {
+            TradeSide::Buy => account.balance -= total,
+            TradeSide::Sell => {
+                let holding = account.holdings.entry(symbol.clone()).or_default();
+                *holding -= quantity;
+                if holding.is_zero() {
+                    account.holdings.remove(&symbol);
+                }
+            }
+        }
+
+        account.open_orders.push(PaperOrder {
+            id: order_id,
+            chat_id: chat_id.0,
+            side,
+            symbol: symbol.clone(),
+            quantity,
+            limit_price,
+            created_at: Utc::now().timestamp(),
+        });
+
+        format!(
+            "Paper limit order #{order_id} placed: {side} {} {symbol} at {limit_price} (market {}).",
+            quantity.normalize(),
+            market_price
+        )
+    };
+
+    storage::save(PAPER_FILE, &*book)?;
+
+    Ok(reply)
+}
+
+pub fn cancel_order(user_id: UserId, order_id: u64) -> Result<(), PaperError> {
+    let mut book = PAPER.lock().expect("paper lock poisoned");
+    let account = book
+        .accounts
+        .get_mut(&user_id.0)
+        .ok_or(PaperError::OrderNotFound(order_id))?;
+
+    let index = account
+        .open_orders
+        .iter()
+        .position(|order| order.id == order_id)
+        .ok_or(PaperError::OrderNotFound(order_id))?;
+    let order = account.open_orders.remove(index);
+
+    match order.side {
+        TradeSide::Buy => account.balance += order.quantity * order.limit_price,
+        TradeSide::Sell => *account.holdings.entry(order.symbol).or_default() += order.quantity,
+    }
+
+    storage::save(PAPER_FILE, &*book)?;
+
+    Ok(())
+}
+
+pub fn reset(user_id: UserId, name: &str) -> Result<(), PaperError> {
+    let mut book = PAPER.lock().expect("paper lock poisoned");
+    book.accounts.insert(user_id.0, new_account(name));
+    storage::save(PAPER_FILE, &*book)?;
+    Ok(())
+}
+
+// Free balance, reserved funds and holdings valued at the latest prices
+fn account_value(account: &PaperAccount, prices: &HashMap<String, f64>) -> Decimal {
+    let price_of = |symbol: &str| {
+        prices
+            .get(symbol)
+            .copied()
+            .and_then(to_decimal)
+            .unwrap_or_default()
+    };
+
+    let holdings: Decimal = account
+        .holdings
+        .iter()
+        .map(|(symbol, quantity)| *quantity * price_of(symbol))
+        .sum();
+
+    let reserved: Decimal = account
+        .open_orders
+        .iter()
+        .map(|order| match order.side {
+            TradeSide::Buy => order.quantity * order.limit_price,
+            TradeSide::Sell => order.quantity * price_of(&order.symbol),
+        })
+        .sum();
+
+    account.balance + holdings + reserved
+}
+
+fn account_symbols(accounts: &[&PaperAccount]) -> Vec<String> {
+    let mut symbols: Vec<String> = accounts
+        .iter()
+        .flat_map(|account| {
+            account
+                .holdings
+                .keys()
+                .cloned()
+                .chain(account.open_orders.iter().map(|order| order.symbol.clone()))
+        })
+        .collect();
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+pub async fn account_summary(user_id: UserId, name: &str) -> Result<String, PaperError> {
+    let account = PAPER
+        .lock()
+        .expect("paper lock poisoned")
+        .accounts
+        .get(&user_id.0)
+        .cloned()
+        .unwrap_or_else(|| new_account(name));
+
+    let prices = crypto_service::prices(&account_symbols(&[&account])).await?;
+    let value = account_value(&account, &prices);
+    let pnl = value - STARTING_BALANCE;
+
+    let mut message = String::from("Paper trading account:\n\n");
+    message.push_str(&format!(
+        "Free Balance: {} {QUOTE_ASSET}\n",
+        account.balance.round_dp(2)
+    ));
+
+    for (symbol, quantity) in &account.holdings {
+        let price = prices
+            .get(symbol)
+            .copied()
+            .and_then(to_decimal)
+            .unwrap_or_default();
+        message.push_str(&format!(
+            "{symbol}: {} (worth {} {QUOTE_ASSET})\n",
+            quantity.normalize(),
+            (*quantity * price).round_dp(2)
+        ));
+    }
+
+    if !account.open_orders.is_empty() {
+        message.push_str("\nOpen orders:\n");
+        for order in &account.open_orders {
+            message.push_str(&format!(
+                "#{} {} {} {} at {}\n",
+                order.id,
+                order.side,
+                order.quantity.normalize(),
+                order.symbol,
+                order.limit_price
+            ));
+        }
+    }
+
+    message.push_str(&format!(
+        "\nTotal Value: {} {QUOTE_ASSET}\n",
+        value.round_dp(2)
+    ));
+    message.push_str(&format!(
+        "P&L: {} {QUOTE_ASSET} ({}%)\n",
+        pnl.round_dp(2),
+        (pnl / STARTING_BALANCE * Decimal::ONE_HUNDRED).round_dp(2)
+    ));
+
+    Ok(message)
+}
+
+pub fn history(user_id: UserId) -> String {
+    let book = PAPER.lock().expect("paper lock poisoned");
+    let Some(account) = book
+        .accounts
+        .get(&user_id.0)
+        .filter(|account| !account.history.is_empty())
+    else {
+        return "No paper trades yet. Try /paper buy BTC 0.01".to_string();
+    };
+
+    let mut message = String::from("Paper trade history:\n\n");
+    for fill in account.history.iter().rev().take(HISTORY_LIMIT) {
+        let time = DateTime::<Utc>::from_timestamp(fill.time, 0)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        message.push_str(&format!(
+            "{time} #{} {} {} {} at {}{}\n",
+            fill.order_id,
+            fill.side,
+            fill.quantity.normalize(),
+            fill.symbol,
+            fill.price,
+            if fill.limit { " (limit)" } else { "" }
+        ));
+    }
+    message
+}
+
+// Members of a group ranked by total account value
+pub async fn leaderboard(chat_id: ChatId) -> Result<String, PaperError> {
+    let accounts: Vec<PaperAccount> = {
+        let book = PAPER.lock().expect("paper lock poisoned");
+        book.chat_members
+            .get(&chat_id.0)
+            .map(|members| {
+                members
+                    .iter()
+                    .filter_map(|member| book.accounts.get(member).cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    if accounts.is_empty() {
+        return Ok(
+            "Nobody in this chat has paper traded yet. Start with /paper buy BTC 0.01".to_string(),
+        );
+    }
+
+    let prices =
+        crypto_service::prices(&account_symbols(&accounts.iter().collect::<Vec<_>>())).await?;
+    let mut ranking: Vec<(String, Decimal)> = accounts
+        .iter()
+        .map(|account| (account.name.clone(), account_value(account, &prices)))
+        .collect();
+    ranking.sort_by_key(|(_, value)| std::cmp::Reverse(*value));
+
+    let mut message = String::from("Paper trading leaderboard:\n\n");
+    for (position, (name, value)) in ranking.iter().enumerate() {
+        let pnl = (*value - STARTING_BALANCE) / STARTING_BALANCE * Decimal::ONE_HUNDRED;
+        message.push_str(&format!(
+            "{}. {} - {} {QUOTE_ASSET} ({}%)\n",
+            position + 1,
+            name,
+            value.round_dp(2),
+            pnl.round_dp(2)
+        ));
+    }
+    Ok(message)
+}
+
+async fn match_orders(bot: &Bot) -> Result<(), PaperError> {
+    let symbols: Vec<String> = {
+        let book = PAPER.lock().expect("paper lock poisoned");
+        let mut symbols: Vec<String> = book
+            .accounts
+            .values()
+            .flat_map(|account| account.open_orders.iter().map(|order| order.symbol.clone()))
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    };
+
+    if symbols.is_empty() {
+        return Ok(());
+    }
+
+    let prices: HashMap<String, Decimal> = crypto_service::prices(&symbols)
+        .await?
+        .into_iter()
+        .filter_map(|(symbol, price)| Some((symbol, to_decimal(price)?)))
+        .collect();
+
+    let mut notifications = Vec::new();
+    {
+        let mut book = PAPER.lock().expect("paper lock poisoned");
+
+        for account in book.accounts.values_mut() {
+            let (filled, open): (Vec<PaperOrder>, Vec<PaperOrder>) =
+                account.open_orders.drain(..).partition(|order| {
+                    prices
+                        .get(&order.symbol)
+                        .is_some_and(|price| match order.side {
+                            TradeSide::Buy => *price <= order.limit_price,
+                            TradeSide::Sell => *price >= order.limit_price,
+                        })
+                });
+            account.open_orders = open;
+
+            for order in filled {
+                let total = notional(order.quantity, order.limit_price).unwrap_or_default();
+                let order_fill = fill(
+                    order.id,
+                    order.side,
+                    &order.symbol,
+                    order.quantity,
+                    order.limit_price,
+                    true,
+                );
+                execute(account, order_fill, total);
+                notifications.push((
+                    ChatId(order.chat_id),
+                    format!(
+                        "Paper limit order #{} filled for {}: {} {} {} at {}",
+                        order.id,
+                        account.name,
+                        order.side,
+                        order.quantity.normalize(),
+                        order.symbol,
+                        order.limit_price
+                    ),
+                ));
+            }
+        }
+
+        if !notifications.is_empty() {
+            storage::save(PAPER_FILE, &*book)?;
+        }
+    }
+
+    for (chat_id, message) in notifications {
+        if let Err(err) = bot.send_message(chat_id, message).await {
+            log::error!("failed to notify paper fill: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+// Background loop filling resting limit orders once the market reaches their price
+pub async fn matcher(bot: Bot) {
+    let mut interval = tokio::time::interval(MATCH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = match_orders(&bot).await {
+            log::error!("paper order matching failed: {}", err);
+        }
+    }
+}
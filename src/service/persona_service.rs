@@ -0,0 +1,53 @@
+use teloxide::types::ChatId;
+
+use crate::{
+    models::{
+        assets::StorageError,
+        persona::{Persona, PersonaChoice},
+    },
+    service::settings_service,
+    utils::data::PERSONA_PRESETS,
+};
+
+pub const MAX_CUSTOM_PROMPT: usize = 1000;
+
+pub fn find(id: &str) -> Option<&'static Persona> {
+    PERSONA_PRESETS
+        .iter()
+        .find(|persona| persona.id.eq_ignore_ascii_case(id))
+}
+
+// System prompt sent before every AI request in this chat
+pub fn system_prompt(chat_id: ChatId) -> Option<String> {
+    match settings_service::get(chat_id).persona? {
+        PersonaChoice::Preset(id) => find(&id).map(|persona| persona.prompt.clone()),
+        PersonaChoice::Custom(prompt) => Some(prompt),
+    }
+}
+
+pub fn set(chat_id: ChatId, choice: Option<PersonaChoice>) -> Result<(), StorageError> {
+    settings_service::update(chat_id, |settings| settings.persona = choice)?;
+    Ok(())
+}
+
+pub fn describe(chat_id: ChatId) -> String {
+    let current = match settings_service::get(chat_id).persona {
+        Some(PersonaChoice::Preset(id)) => find(&id)
+            .map(|persona| persona.name.clone())
+            .unwrap_or_else(|| "default".to_string()),
+        Some(PersonaChoice::Custom(prompt)) => format!("custom: \"{prompt}\""),
+        None => "default".to_string(),
+    };
+
+    let mut message = format!("Current persona: {current}\n\nPresets:\n");
+    for persona in PERSONA_PRESETS.iter() {
+        message.push_str(&format!(
+            "{} - {}: {}\n",
+            persona.id, persona.name, persona.description
+        ));
+    }
+    message.push_str(
+        "\nUse /persona <preset>, /persona custom <instructions> or /persona default to go back.",
+    );
+    message
+}
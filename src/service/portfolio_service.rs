@@ -0,0 +1,431 @@
+use chrono::{NaiveDate, Utc};
+use lazy_static::lazy_static;
+use rust_decimal::Decimal;
+use std::{collections::HashMap, str::FromStr, sync::Mutex};
+use teloxide::types::UserId;
+
+use crate::{
+    models::{
+        assets::PortfolioError,
+        portfolio::{Position, Trade, TradeSide},
+    },
+    service::crypto_service::{self, to_decimal},
+    utils::storage,
+};
+
+const PORTFOLIO_FILE: &str = "portfolios.json";
+
+lazy_static! {
+    static ref LEDGERS: Mutex<HashMap<u64, Vec<Trade>>> = Mutex::new(storage::load(PORTFOLIO_FILE));
+}
+
+fn ledger(user_id: UserId) -> Vec<Trade> {
+    let mut trades = LEDGERS
+        .lock()
+        .expect("portfolio lock poisoned")
+        .get(&user_id.0)
+        .cloned()
+        .unwrap_or_default();
+    trades.sort_by_key(|trade| trade.date);
+    trades
+}
+
+fn parse_decimal(value: &str) -> Option<Decimal> {
+    Decimal::from_str(&value.replace(',', ""))
+        .ok()
+        .filter(|number| number.is_sign_positive() && !number.is_zero())
+}
+
+// "<pair> <quantity> [price] [YYYY-MM-DD]", only trades of today may leave out the price to use the live one
+pub fn parse_trade(
+    args: &str,
+) -> Result<(String, Decimal, Option<Decimal>, NaiveDate), PortfolioError> {
+    let invalid = || PortfolioError::InvalidTrade(args.trim().to_string());
+    let words: Vec<&str> = args.split_whitespace().collect();
+
+    let (symbol, quantity, rest) = match words.as_slice() {
+        [symbol, quantity, rest @ ..] => (symbol, quantity, rest),
+        _ => return Err(invalid()),
+    };
+
+    let symbol = crypto_service::normalize_symbol(symbol)?;
+    let quantity = parse_decimal(quantity).ok_or_else(invalid)?;
+
+    let mut price = None;
+    let mut date = Utc::now().date_naive();
+
+    for word in rest {
+        if let Ok(parsed) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+            date = parsed;
+        } else if price.is_none() {
+            price = Some(parse_decimal(word).ok_or_else(invalid)?);
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    if price.is_none() && date < Utc::now().date_naive() {
+        return Err(PortfolioError::MissingPrice(date.to_string()));
+    }
+
+    if price.is_some_and(|price| quantity.checked_mul(price).is_none()) {
+        return Err(PortfolioError::TooLarge(args.trim().to_string()));
+    }
+
+    Ok((symbol, quantity, price, date))
+}
+
+fn too_large(trade: &Trade) -> PortfolioError {
+    PortfolioError::TooLarge(format!(
+        "{} {} @ {}",
+        trade.symbol,
+        trade.quantity.normalize(),
+        trade.price.normalize()
+    ))
+}
+
+fn apply(position: &mut Position, trade: &Trade) -> Result<(), PortfolioError> {
+    match trade.side {
+        TradeSide::Buy => {
+            let cost = trade
+                .quantity
+                .checked_mul(trade.price)
+                .ok_or_else(|| too_large(trade))?;
+            position.quantity = position
+                .quantity
+                .checked_add(trade.quantity)
+                .ok_or_else(|| too_large(trade))?;
+            position.cost_basis = position
+                .cost_basis
+                .checked_add(cost)
+                .ok_or_else(|| too_large(trade))?;
+        }
+        TradeSide::Sell => {
+            if trade.quantity > position.quantity {
+                return Err(PortfolioError::InsufficientHoldings {
+                    symbol: trade.symbol.clone(),
+                    requested: trade.quantity.normalize().to_string(),
+                    held: position.quantity.normalize().to_string(),
+                    date: trade.date.to_string(),
+                });
+            }
+            let average_cost = position.cost_basis / position.quantity;
+            let realized = (trade.price - average_cost)
+                .checked_mul(trade.quantity)
+                .and_then(|pnl| position.realized_pnl.checked_add(pnl))
+                .ok_or_else(|| too_large(trade))?;
+            position.realized_pnl = realized;
+            position.cost_basis -= average_cost * trade.quantity;
+            position.quantity -= trade.quantity;
+        }
+    }
+
+    Ok(())
+}
+
+fn position_mut<'a>(positions: &'a mut Vec<Position>, symbol: &str) -> &'a mut Position {
+    let index = match positions
+        .iter()
+        .position(|position| position.symbol == symbol)
+    {
+        Some(index) => index,
+        None => {
+            positions.push(Position {
+                symbol: symbol.to_string(),
+                ..Position::default()
+            });
+            positions.len() - 1
+        }
+    };
+    &mut positions[index]
+}
+
+// Replays the ledger in date order and fails on the first trade it cannot apply
+fn replay(trades: &[Trade]) -> Result<Vec<Position>, PortfolioError> {
+    let mut positions: Vec<Position> = Vec::new();
+
+    for trade in trades {
+        apply(position_mut(&mut positions, &trade.symbol), trade)?;
+    }
+
+    Ok(positions)
+}
+
+// Ledgers written before trades were replayed on record may hold oversells, those trades are left out
+fn build_positions(trades: &[Trade]) -> Vec<Position> {
+    let mut positions: Vec<Position> = Vec::new();
+
+    for trade in trades {
+        let position = position_mut(&mut positions, &trade.symbol);
+        let mut updated = position.clone();
+        if apply(&mut updated, trade).is_ok() {
+            *position = updated;
+        }
+    }
+
+    positions
+}
+
+pub async fn record(user_id: UserId, side: TradeSide, args: &str) -> Result<Trade, PortfolioError> {
+    let (symbol, quantity, price, date) = parse_trade(args)?;
+
+    let price = match price {
+        Some(price) => price,
+        None => {
+            let ticker = crypto_service::ticker(&symbol).await?;
+            to_decimal(ticker.last_price)
+                .ok_or_else(|| PortfolioError::InvalidTrade(args.trim().to_string()))?
+        }
+    };
+
+    let trade = Trade {
+        side,
+        symbol,
+        quantity,
+        price,
+        date,
+    };
+
+    let mut ledgers = LEDGERS.lock().expect("portfolio lock poisoned");
+
+    // A backdated trade has to fit the holdings on its own date and keep every later sell valid
+    let mut trades = ledgers.get(&user_id.0).cloned().unwrap_or_default();
+    trades.sort_by_key(|trade| trade.date);
+    let at = trades.partition_point(|existing| existing.date <= trade.date);
+    trades.insert(at, trade.clone());
+    replay(&trades)?;
+
+    ledgers.entry(user_id.0).or_default().push(trade.clone());
+    storage::save(PORTFOLIO_FILE, &*ledgers)?;
+
+    Ok(trade)
+}
+
+pub async fn positions(user_id: UserId) -> Result<Vec<Position>, PortfolioError> {
+    let mut positions = build_positions(&ledger(user_id));
+
+    let symbols: Vec<String> = positions
+        .iter()
+        .filter(|position| !position.quantity.is_zero())
+        .map(|position| position.symbol.clone())
+        .collect();
+
+    if !symbols.is_empty() {
+        let prices = crypto_service::prices(&symbols).await?;
+        for position in positions.iter_mut() {
+            position.market_price = prices.get(&position.symbol).copied().and_then(to_decimal);
+        }
+    }
+
+    Ok(positions)
+}
+
+// Values are in each pair's quote asset, which is USDT unless the pair says otherwise
+pub async fn summary(user_id: UserId) -> Result<String, PortfolioError> {
+    let positions = positions(user_id).await?;
+
+    if positions.is_empty() {
+        return Ok(
+            "Your portfolio is empty. Record a trade with /buy BTCUSDT 0.1 60000".to_string(),
+        );
+    }
+
+    let total_value: Decimal = positions.iter().filter_map(Position::market_value).sum();
+    let total_cost: Decimal = positions.iter().map(|position| position.cost_basis).sum();
+    let total_realized: Decimal = positions.iter().map(|position| position.realized_pnl).sum();
+    let total_unrealized: Decimal = positions.iter().filter_map(Position::unrealized_pnl).sum();
+
+    let mut message = String::from("Portfolio:\n\n");
+
+    for position in &positions {
+        message.push_str(&format!("{}\n", position.symbol));
+        message.push_str(&format!("Quantity: {}\n", position.quantity.normalize()));
+        message.push_str(&format!(
+            "Cost Basis: {}\n",
+            position.cost_basis.round_dp(2)
+        ));
+
+        match (position.market_value(), position.unrealized_pnl()) {
+            (Some(value), Some(unrealized)) => {
+                let allocation = if total_value.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    value / total_value * Decimal::ONE_HUNDRED
+                };
+                message.push_str(&format!("Value: {}\n", value.round_dp(2)));
+                message.push_str(&format!("Unrealized P&L: {}\n", unrealized.round_dp(2)));
+                message.push_str(&format!("Allocation: {}%\n", allocation.round_dp(2)));
+            }
+            _ => message.push_str("Value: price unavailable\n"),
+        }
+
+        message.push_str(&format!(
+            "Realized P&L: {}\n",
+            position.realized_pnl.round_dp(2)
+        ));
+        message.push_str("--------------------\n");
+    }
+
+    message.push_str(&format!("Total Value: {}\n", total_value.round_dp(2)));
+    message.push_str(&format!("Total Cost Basis: {}\n", total_cost.round_dp(2)));
+    message.push_str(&format!(
+        "Unrealized P&L: {}\n",
+        total_unrealized.round_dp(2)
+    ));
+    message.push_str(&format!("Realized P&L: {}\n", total_realized.round_dp(2)));
+
+    Ok(message)
+}
+
+pub fn export_csv(user_id: UserId) -> Vec<u8> {
+    let mut csv = String::from("date,side,symbol,quantity,price,total\n");
+
+    for trade in ledger(user_id) {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            trade.date,
+            trade.side,
+            trade.symbol,
+            trade.quantity.normalize(),
+            trade.price.normalize(),
+            trade
+                .quantity
+                .checked_mul(trade.price)
+                .map(|total| total.normalize().to_string())
+                .unwrap_or_default()
+        ));
+    }
+
+    csv.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(side: TradeSide, quantity: i64, price: i64, date: &str) -> Trade {
+        Trade {
+            side,
+            symbol: "BTCUSDT".to_string(),
+            quantity: Decimal::from(quantity),
+            price: Decimal::from(price),
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+        }
+    }
+
+    #[test]
+    fn parses_trades() {
+        let (symbol, quantity, price, date) = parse_trade("btc 0.5 60,000 2024-03-01").unwrap();
+
+        assert_eq!(symbol, "BTCUSDT");
+        assert_eq!(quantity, Decimal::from_str("0.5").unwrap());
+        assert_eq!(price, Some(Decimal::from(60000)));
+        assert_eq!(date, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+
+        let (_, _, price, date) = parse_trade("ETHUSDT 2").unwrap();
+        assert_eq!(price, None);
+        assert_eq!(date, Utc::now().date_naive());
+    }
+
+    #[test]
+    fn rejects_malformed_trades() {
+        for text in [
+            "",
+            "BTC",
+            "BTC 0",
+            "BTC -1",
+            "BTC 1 abc",
+            "BTC 1 100 200",
+            "BTC 1 -100",
+        ] {
+            assert!(
+                matches!(parse_trade(text), Err(PortfolioError::InvalidTrade(_))),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_trades() {
+        let text = format!("BTC {} 2", Decimal::MAX);
+
+        assert!(matches!(
+            parse_trade(&text),
+            Err(PortfolioError::TooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn requires_a_price_for_backdated_trades() {
+        assert!(matches!(
+            parse_trade("BTC 1 2024-03-01"),
+            Err(PortfolioError::MissingPrice(_))
+        ));
+
+        let (_, _, price, _) = parse_trade("BTC 1 60000 2024-03-01").unwrap();
+        assert_eq!(price, Some(Decimal::from(60000)));
+
+        let today = Utc::now().date_naive().to_string();
+        assert!(parse_trade(&format!("BTC 1 {today}")).is_ok());
+    }
+
+    #[test]
+    fn replay_tracks_average_cost() {
+        let positions = replay(&[
+            trade(TradeSide::Buy, 2, 100, "2024-01-01"),
+            trade(TradeSide::Buy, 2, 200, "2024-01-02"),
+            trade(TradeSide::Sell, 1, 300, "2024-01-03"),
+        ])
+        .unwrap();
+
+        assert_eq!(positions[0].quantity, Decimal::from(3));
+        assert_eq!(positions[0].cost_basis, Decimal::from(450));
+        assert_eq!(positions[0].realized_pnl, Decimal::from(150));
+    }
+
+    #[test]
+    fn replay_rejects_sell_before_buy() {
+        let result = replay(&[
+            trade(TradeSide::Sell, 1, 100, "2024-01-01"),
+            trade(TradeSide::Buy, 1, 100, "2024-01-02"),
+        ]);
+
+        assert!(matches!(
+            result,
+            Err(PortfolioError::InsufficientHoldings { .. })
+        ));
+    }
+
+    #[test]
+    fn replay_rejects_backdated_sell_that_breaks_a_later_sell() {
+        let result = replay(&[
+            trade(TradeSide::Buy, 1, 100, "2024-01-01"),
+            trade(TradeSide::Sell, 1, 100, "2024-01-02"),
+            trade(TradeSide::Sell, 1, 100, "2024-01-03"),
+        ]);
+
+        assert!(matches!(
+            result,
+            Err(PortfolioError::InsufficientHoldings { .. })
+        ));
+    }
+
+    #[test]
+    fn replay_rejects_overflowing_trade() {
+        let mut huge = trade(TradeSide::Buy, 1, 1, "2024-01-01");
+        huge.quantity = Decimal::MAX;
+        huge.price = Decimal::from(2);
+
+        assert!(matches!(replay(&[huge]), Err(PortfolioError::TooLarge(_))));
+    }
+
+    #[test]
+    fn build_positions_skips_oversells() {
+        let positions = build_positions(&[
+            trade(TradeSide::Sell, 1, 100, "2024-01-01"),
+            trade(TradeSide::Buy, 1, 100, "2024-01-02"),
+        ]);
+
+        assert_eq!(positions[0].quantity, Decimal::ONE);
+    }
+}
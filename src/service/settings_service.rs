@@ -0,0 +1,39 @@
+use lazy_static::lazy_static;
+use std::{collections::HashMap, sync::Mutex};
+use teloxide::types::ChatId;
+
+use crate::{
+    models::{assets::StorageError, settings::ChatSettings},
+    utils::storage,
+};
+
+const SETTINGS_FILE: &str = "chat_settings.json";
+
+lazy_static! {
+    static ref SETTINGS: Mutex<HashMap<i64, ChatSettings>> =
+        Mutex::new(storage::load(SETTINGS_FILE));
+}
+
+pub fn get(chat_id: ChatId) -> ChatSettings {
+    SETTINGS
+        .lock()
+        .expect("settings lock poisoned")
+        .get(&chat_id.0)
+        .cloned()
+        .unwrap_or_default()
+}
+
+// Apply a change to the chat settings and persist the whole table
+pub fn update<F>(chat_id: ChatId, change: F) -> Result<ChatSettings, StorageError>
+where
+    F: FnOnce(&mut ChatSettings),
+{
+    let mut settings = SETTINGS.lock().expect("settings lock poisoned");
+    let entry = settings.entry(chat_id.0).or_default();
+    change(entry);
+    let updated = entry.clone();
+
+    storage::save(SETTINGS_FILE, &*settings)?;
+
+    Ok(updated)
+}
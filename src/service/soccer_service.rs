@@ -6,9 +6,10 @@ use std::time::SystemTime;
 
 use crate::models::soccer::TodayApiResponse;
 
-pub async fn today_events() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn fetch_today_events(
+) -> Result<TodayApiResponse, Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Fetching today's events");
-    let rapidapi_key = env::var("RAPIDAPI_KEY").expect("RAPIDAPI_KEY must be set");
+    let rapidapi_key = env::var("RAPIDAPI_KEY").map_err(|_| "RAPIDAPI_KEY must be set")?;
     let sys_time = SystemTime::now();
     let date_time: DateTime<Utc> = sys_time.into();
     let formatted_date = date_time.format("%Y-%m-%d").to_string();
@@ -19,7 +20,7 @@ pub async fn today_events() -> Result<String, Box<dyn std::error::Error + Send +
         formatted_date
     );
 
-    log::info!("{url}");
+    log::debug!("{url}");
 
     let response = client
         .get(url)
@@ -29,10 +30,15 @@ pub async fn today_events() -> Result<String, Box<dyn std::error::Error + Send +
         .await?;
 
     let body: Value = response.json().await?;
-    log::info!("this is the data: {:?}", body);
 
     let response_object: TodayApiResponse = serde_json::from_value(body)?;
 
+    Ok(response_object)
+}
+
+pub async fn today_events() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let response_object = fetch_today_events().await?;
+
     let mut message = String::from("Today's events:\n\n");
     // for (index, event) in body.events.iter().enumerate() {
     //     message.push_str(&format!("Event {}:\n", index + 1));
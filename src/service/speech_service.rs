@@ -0,0 +1,212 @@
+use reqwest::multipart::{Form, Part};
+use std::env;
+use teloxide::prelude::*;
+
+use crate::{
+    models::{
+        assets::SpeechError,
+        llm::{Provider, TokenUsage},
+        speech::{SpeechRequest, TranscriptionResponse},
+    },
+    service::{
+        llm_service::{self, DEFAULT_BASE_URL},
+        usage_service,
+    },
+    utils::helpers,
+};
+
+const DEFAULT_TRANSCRIPTION_MODEL: &str = "whisper-1";
+const DEFAULT_SPEECH_MODEL: &str = "tts-1";
+const DEFAULT_VOICE: &str = "alloy";
+pub const MAX_VOICE_SECONDS: u32 = 300;
+// Bots can't download files over 20 MB anyway
+const MAX_VOICE_MB: u32 = 20;
+// Longest input the speech endpoint accepts
+const MAX_SPEECH_CHARS: usize = 4096;
+// Speech shares the AI quota in token equivalents, a second of audio counts as 10 tokens and a spoken character as one
+const TOKENS_PER_AUDIO_SECOND: u64 = 10;
+
+// Follows the LLM settings unless SPEECH_PROVIDER, SPEECH_BASE_URL or SPEECH_API_KEY say otherwise,
+// so a local whisper server can sit next to any chat model
+pub fn provider() -> Provider {
+    let name = env::var("SPEECH_PROVIDER")
+        .or_else(|_| env::var("LLM_PROVIDER"))
+        .unwrap_or_default();
+
+    match name.to_lowercase().as_str() {
+        "mock" => Provider::Mock { tool: None },
+        _ => Provider::OpenAiCompatible {
+            base_url: env::var("SPEECH_BASE_URL")
+                .or_else(|_| env::var("LLM_BASE_URL"))
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+            api_key: env::var("SPEECH_API_KEY")
+                .or_else(|_| env::var("LLM_API_KEY"))
+                .or_else(|_| env::var("GPT_API_KEY"))
+                .ok(),
+        },
+    }
+}
+
+fn setting(name: &str, default: &str) -> String {
+    env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+// The model usage is billed under, the mock's answers are free
+fn model(name: &str, default: &str) -> String {
+    match provider() {
+        Provider::Mock { .. } => "mock".to_string(),
+        Provider::OpenAiCompatible { .. } => setting(name, default),
+    }
+}
+
+// A failure to store usage shouldn't lose the transcript or the spoken reply
+fn bill(user_id: UserId, chat_id: ChatId, model: &str, usage: TokenUsage) {
+    if let Err(err) = usage_service::record(user_id, chat_id, model, usage) {
+        log::error!(
+            "Failed to record speech usage of {} in {}: {}",
+            user_id,
+            chat_id,
+            err
+        );
+    }
+}
+
+pub async fn transcribe(
+    audio: Vec<u8>,
+    file_name: &str,
+    mime: &str,
+) -> Result<String, SpeechError> {
+    let (base_url, api_key) = match provider() {
+        Provider::Mock { .. } => {
+            return Ok(format!(
+                "Mock transcription of {} bytes of audio",
+                audio.len()
+            ))
+        }
+        Provider::OpenAiCompatible { base_url, api_key } => (base_url, api_key),
+    };
+
+    let form = Form::new()
+        .part(
+            "file",
+            Part::bytes(audio)
+                .file_name(file_name.to_string())
+                .mime_str(mime)?,
+        )
+        .text(
+            "model",
+            setting("SPEECH_MODEL", DEFAULT_TRANSCRIPTION_MODEL),
+        );
+
+    let builder = reqwest::Client::new().post(format!("{base_url}/audio/transcriptions"));
+    let response = llm_service::authorize(builder, &base_url, api_key.as_deref())?
+        .multipart(form)
+        .send()
+        .await?;
+    let transcription: TranscriptionResponse =
+        llm_service::check_status(response).await?.json().await?;
+
+    Ok(transcription.text.trim().to_string())
+}
+
+// Transcribes the voice note or audio file of a message, None when it has neither.
+// The quota is checked before anything is downloaded and the audio's length is billed to `user_id`
+pub async fn transcribe_message(
+    bot: &Bot,
+    msg: &Message,
+    user_id: UserId,
+) -> Result<Option<String>, SpeechError> {
+    let (file, seconds, file_name, mime) = match (msg.voice(), msg.audio()) {
+        (Some(voice), _) => (
+            &voice.file,
+            voice.duration.seconds(),
+            "voice.ogg".to_string(),
+            voice.mime_type.as_ref(),
+        ),
+        (None, Some(audio)) => (
+            &audio.file,
+            audio.duration.seconds(),
+            audio
+                .file_name
+                .clone()
+                .unwrap_or_else(|| "audio.mp3".to_string()),
+            audio.mime_type.as_ref(),
+        ),
+        _ => return Ok(None),
+    };
+
+    if seconds > MAX_VOICE_SECONDS {
+        return Err(SpeechError::TooLong {
+            seconds,
+            limit: MAX_VOICE_SECONDS,
+        });
+    }
+    if file.size > MAX_VOICE_MB * 1024 * 1024 {
+        return Err(SpeechError::TooLarge {
+            limit: MAX_VOICE_MB,
+        });
+    }
+
+    usage_service::check_quota(user_id, msg.chat.id)?;
+
+    let mime = mime.map_or("audio/ogg".to_string(), |mime| mime.to_string());
+    let audio = helpers::download_file::<SpeechError>(bot, &file.id).await?;
+    let transcript = transcribe(audio, &file_name, &mime).await?;
+
+    let usage = TokenUsage {
+        prompt_tokens: u64::from(seconds.max(1)) * TOKENS_PER_AUDIO_SECOND,
+        completion_tokens: 0,
+    };
+    bill(
+        user_id,
+        msg.chat.id,
+        &model("SPEECH_MODEL", DEFAULT_TRANSCRIPTION_MODEL),
+        usage,
+    );
+
+    Ok(Some(transcript))
+}
+
+// OGG Opus audio, the format Telegram plays as a voice message. Billed to `user_id` by the characters spoken
+pub async fn synthesize(
+    user_id: UserId,
+    chat_id: ChatId,
+    text: &str,
+) -> Result<Vec<u8>, SpeechError> {
+    let (base_url, api_key) = match provider() {
+        Provider::Mock { .. } => return Err(SpeechError::Unsupported("speak")),
+        Provider::OpenAiCompatible { base_url, api_key } => (base_url, api_key),
+    };
+    usage_service::check_quota(user_id, chat_id)?;
+
+    let input: String = text.chars().take(MAX_SPEECH_CHARS).collect();
+    let model = setting("TTS_MODEL", DEFAULT_SPEECH_MODEL);
+    let voice = setting("TTS_VOICE", DEFAULT_VOICE);
+    let request = SpeechRequest {
+        model: &model,
+        input: &input,
+        voice: &voice,
+        response_format: "opus",
+    };
+
+    let builder = reqwest::Client::new().post(format!("{base_url}/audio/speech"));
+    let response = llm_service::authorize(builder, &base_url, api_key.as_deref())?
+        .json(&request)
+        .send()
+        .await?;
+
+    let audio = llm_service::check_status(response)
+        .await?
+        .bytes()
+        .await?
+        .to_vec();
+
+    let usage = TokenUsage {
+        prompt_tokens: 0,
+        completion_tokens: input.chars().count() as u64,
+    };
+    bill(user_id, chat_id, &model, usage);
+
+    Ok(audio)
+}
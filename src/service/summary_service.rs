@@ -0,0 +1,167 @@
+use lazy_static::lazy_static;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+use teloxide::types::{ChatId, Message, MessageEntityKind};
+
+use crate::{
+    models::{assets::StorageError, summary::SeenMessage},
+    service::settings_service,
+    utils::tokens::estimate_tokens,
+};
+
+pub const DEFAULT_SUMMARY_MESSAGES: usize = 100;
+pub const MAX_SUMMARY_MESSAGES: usize = 500;
+const MAX_FORWARDED: usize = 200;
+// Oldest messages are left out when a transcript would not fit the model comfortably
+const MAX_TRANSCRIPT_TOKENS: usize = 8000;
+
+lazy_static! {
+    static ref GROUP_MESSAGES: Mutex<HashMap<i64, VecDeque<SeenMessage>>> =
+        Mutex::new(HashMap::new());
+    static ref FORWARDED: Mutex<HashMap<i64, Vec<SeenMessage>>> = Mutex::new(HashMap::new());
+}
+
+// Forwarded messages are credited to whoever wrote them originally
+fn author(msg: &Message) -> String {
+    msg.forward_from_user()
+        .map(|user| user.first_name.clone())
+        .or_else(|| msg.forward_from_sender_name().map(str::to_string))
+        .or_else(|| {
+            msg.forward_from_chat()
+                .and_then(|chat| chat.title())
+                .map(str::to_string)
+        })
+        .or_else(|| msg.from.as_ref().map(|user| user.first_name.clone()))
+        .unwrap_or_else(|| "Someone".to_string())
+}
+
+fn seen_message(msg: &Message) -> Option<SeenMessage> {
+    let text = msg.text().or_else(|| msg.caption())?;
+    if text.starts_with('/') {
+        return None;
+    }
+
+    let entities = msg
+        .parse_entities()
+        .or_else(|| msg.parse_caption_entities())
+        .unwrap_or_default();
+    let links = entities
+        .iter()
+        .filter_map(|entity| match entity.kind() {
+            MessageEntityKind::Url => Some(entity.text().to_string()),
+            MessageEntityKind::TextLink { url } => Some(url.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    Some(SeenMessage {
+        author: author(msg),
+        text: text.to_string(),
+        links,
+        date: msg.forward_date().unwrap_or(msg.date),
+    })
+}
+
+// Called for every incoming message, only groups that opted in are kept
+pub fn observe(msg: Message) {
+    if msg.chat.is_private() || !settings_service::get(msg.chat.id).summaries {
+        return;
+    }
+    let Some(seen) = seen_message(&msg) else {
+        return;
+    };
+
+    let mut buffers = GROUP_MESSAGES.lock().expect("group messages lock poisoned");
+    let buffer = buffers.entry(msg.chat.id.0).or_default();
+    buffer.push_back(seen);
+    if buffer.len() > MAX_SUMMARY_MESSAGES {
+        buffer.pop_front();
+    }
+}
+
+pub fn recent(chat_id: ChatId, count: usize) -> Vec<SeenMessage> {
+    let buffers = GROUP_MESSAGES.lock().expect("group messages lock poisoned");
+    let Some(buffer) = buffers.get(&chat_id.0) else {
+        return Vec::new();
+    };
+
+    buffer
+        .iter()
+        .skip(buffer.len().saturating_sub(count))
+        .cloned()
+        .collect()
+}
+
+// Turning summaries off also forgets what was collected
+pub fn set_enabled(chat_id: ChatId, enabled: bool) -> Result<(), StorageError> {
+    settings_service::update(chat_id, |settings| settings.summaries = enabled)?;
+    if !enabled {
+        GROUP_MESSAGES
+            .lock()
+            .expect("group messages lock poisoned")
+            .remove(&chat_id.0);
+    }
+    Ok(())
+}
+
+// Returns how many forwarded messages are waiting to be summarized
+pub fn collect_forwarded(msg: &Message) -> usize {
+    let mut forwarded = FORWARDED.lock().expect("forwarded lock poisoned");
+    let batch = forwarded.entry(msg.chat.id.0).or_default();
+
+    if let Some(seen) = seen_message(msg) {
+        if batch.len() < MAX_FORWARDED {
+            batch.push(seen);
+        }
+    }
+    batch.len()
+}
+
+pub fn forwarded(chat_id: ChatId) -> Vec<SeenMessage> {
+    FORWARDED
+        .lock()
+        .expect("forwarded lock poisoned")
+        .get(&chat_id.0)
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn clear_forwarded(chat_id: ChatId) {
+    FORWARDED
+        .lock()
+        .expect("forwarded lock poisoned")
+        .remove(&chat_id.0);
+}
+
+fn transcript_line(message: &SeenMessage) -> String {
+    let mut line = format!(
+        "[{}] {}: {}",
+        message.date.format("%d.%m %H:%M"),
+        message.author,
+        message.text
+    );
+    if !message.links.is_empty() {
+        line.push_str(&format!(" (links: {})", message.links.join(", ")));
+    }
+    line.push('\n');
+    line
+}
+
+// Newest messages are kept when the whole batch would be too long
+pub fn transcript(messages: &[SeenMessage]) -> String {
+    let mut lines = Vec::new();
+    let mut tokens = 0;
+
+    for line in messages.iter().rev().map(transcript_line) {
+        tokens += estimate_tokens(&line);
+        if tokens > MAX_TRANSCRIPT_TOKENS && !lines.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    lines.reverse();
+    lines.concat()
+}
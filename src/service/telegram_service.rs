@@ -1,7 +1,7 @@
 use log::{error, info};
 use reqwest::Response;
-use std::env;
-use tokio::task;
+use std::{env, ops::ControlFlow, sync::Arc, time::Duration};
+use tokio::{sync::watch, task};
 
 use teloxide::{
     dispatching::{
@@ -9,24 +9,61 @@ use teloxide::{
         UpdateHandler,
     },
     prelude::*,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup},
+    types::{
+        ChatAction, InlineKeyboardButton, InlineKeyboardMarkup, InlineQueryResult,
+        InlineQueryResultArticle, InputFile, InputMedia, InputMediaPhoto, InputMessageContent,
+        InputMessageContentText, KeyboardButton, KeyboardMarkup, Me, MediaKind, MediaText,
+        MessageEntityKind, MessageId, MessageKind, ParseMode, ReplyParameters, UpdateId,
+        UpdateKind,
+    },
     utils::command::BotCommands,
 };
 
-use crate::service::gpt_service::gpt_quick_reply;
-use crate::utils::environment::init_vars;
+use crate::utils::environment::{init_vars, is_bot_admin};
 use crate::{
     models::{
-        assets::MessageError,
+        assets::{AiError, CryptoError, MessageError, SpeechError, VisionError},
+        conversation::{ChatRole, ChatTurn, ContextPolicy, MAX_CONTEXT_BUDGET, MIN_CONTEXT_BUDGET},
         orders::{Command as OtherCommand, State},
+        persona::PersonaChoice,
+        portfolio::TradeSide,
+        settings::is_valid_language,
+        speech::InputSource,
+        usage::{QuotaLimits, DEFAULT_DAILY_TOKENS, DEFAULT_MONTHLY_TOKENS},
+    },
+    service::{
+        alert_service, backtest_service, conversion_service, crypto_service, gpt_service,
+        indicator_service, llm_service, moderation_service, movie_service, paper_service,
+        persona_service, portfolio_service, settings_service, soccer_service, speech_service,
+        summary_service, thread_service, usage_service, vision_service, watchlist_service,
+    },
+    utils::{
+        custom_error_handler::CustomErrorHandler,
+        data::PROMPT_DATA,
+        helpers::{can_change_settings, keep_typing, split_message},
     },
-    service::{movie_service, soccer_service},
-    utils::{custom_error_handler::CustomErrorHandler, data::PROMPT_DATA},
 };
 
 type MyDialogue = Dialogue<State, InMemStorage<State>>;
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+const CHART_CALLBACK_PREFIX: &str = "chart:";
+const ALERT_DELETE_PREFIX: &str = "alert:delete:";
+const BOOK_CALLBACK_PREFIX: &str = "book:";
+const MAX_BOOK_LEVELS: usize = 20;
+
+const CHAT_WITH_AI: &str = "Chat with AI";
+const MESSAGE_LIMIT: usize = 4096;
+// Telegram rate limits edits, so streamed replies are redrawn at most this often
+const STREAM_EDIT_INTERVAL: Duration = Duration::from_millis(1500);
+
+const TA_USAGE: &str = "Usage: /ta BTCUSDT 4h, add chart to draw the indicators: /ta ETH 1d chart";
+
+const ALERT_HELP: &str = "Create an alert by sending e.g.\n\
+    BTCUSDT above 70000\n\
+    BTCUSDT below 60000\n\
+    ETH moves 5% in 1h";
+
 #[tokio::main]
 pub async fn main() {
     let token = env::var("TELOXIDE_TOKEN").expect("expect teloxide token to be set");
@@ -36,10 +73,17 @@ pub async fn main() {
 
     let bot_task = task::spawn(async move {
         let bot = Bot::new(token);
+        task::spawn(crypto_service::market_data());
+        task::spawn(alert_service::monitor(bot.clone()));
+        task::spawn(paper_service::matcher(bot.clone()));
+
         let handler = dptree::entry().branch(schema()); // Assuming schema() is defined elsewhere
 
         Dispatcher::builder(bot, handler)
-            .dependencies(dptree::deps![InMemStorage::<State>::new()])
+            .dependencies(dptree::deps![
+                InMemStorage::<State>::new(),
+                InputSource::Text
+            ])
             //  .error_handler(Arc::new(CustomErrorHandler{}))
             .enable_ctrlc_handler()
             .build()
@@ -56,29 +100,108 @@ pub async fn main() {
 pub fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
     use dptree::case;
 
-    let command_handler = teloxide::filter_command::<OtherCommand, _>().branch(
-        case![State::Start]
-            .branch(case![OtherCommand::Help].endpoint(help))
-            .branch(case![OtherCommand::Start].endpoint(start))
-            .branch(case![OtherCommand::Cancel].endpoint(cancel)),
-    );
+    let command_handler = teloxide::filter_command::<OtherCommand, _>()
+        .branch(case![OtherCommand::Adult(value)].endpoint(set_adult))
+        .branch(case![OtherCommand::Movie(query)].endpoint(search_movie))
+        .branch(case![OtherCommand::Language(value)].endpoint(set_language))
+        .branch(case![OtherCommand::Alert(args)].endpoint(manage_alerts))
+        .branch(case![OtherCommand::Buy(args)].endpoint(record_buy))
+        .branch(case![OtherCommand::Sell(args)].endpoint(record_sell))
+        .branch(case![OtherCommand::Portfolio(args)].endpoint(show_portfolio))
+        .branch(case![OtherCommand::Watch(args)].endpoint(watch_pairs))
+        .branch(case![OtherCommand::Unwatch(args)].endpoint(unwatch_pairs))
+        .branch(case![OtherCommand::Watchlist].endpoint(show_watchlist))
+        .branch(case![OtherCommand::Movers].endpoint(show_top_movers))
+        .branch(case![OtherCommand::Convert(args)].endpoint(convert_currency))
+        .branch(case![OtherCommand::Book(args)].endpoint(show_order_book))
+        .branch(case![OtherCommand::Paper(args)].endpoint(paper_trade))
+        .branch(case![OtherCommand::Leaderboard].endpoint(show_leaderboard))
+        .branch(case![OtherCommand::Ta(args)].endpoint(technical_analysis))
+        .branch(case![OtherCommand::Backtest(args)].endpoint(run_backtest))
+        .branch(case![OtherCommand::Reset].endpoint(reset_conversation))
+        .branch(case![OtherCommand::Persona(args)].endpoint(set_persona))
+        .branch(case![OtherCommand::Context(args)].endpoint(manage_context))
+        .branch(case![OtherCommand::Usage(args)].endpoint(show_usage))
+        .branch(case![OtherCommand::Model(args)].endpoint(choose_model))
+        .branch(case![OtherCommand::Summarize(args)].endpoint(summarize_chat))
+        .branch(case![OtherCommand::Voice(args)].endpoint(set_voice_replies))
+        .branch(
+            case![State::ChatWithAi].branch(case![OtherCommand::Cancel].endpoint(leave_ai_chat)),
+        )
+        .branch(
+            case![State::Start]
+                .branch(case![OtherCommand::Help].endpoint(help))
+                .branch(case![OtherCommand::Start].endpoint(start))
+                .branch(case![OtherCommand::Cancel].endpoint(cancel)),
+        );
 
-    let message_handler = Update::filter_message().branch(command_handler).branch(
-        case![State::ReceiveFullName]
-            .endpoint(receive_full_name)
-            .branch(dptree::endpoint(invalid_state)),
-    );
+    let message_handler = Update::filter_message()
+        .inspect(summary_service::observe)
+        .branch(command_handler)
+        .branch(
+            dptree::filter(|msg: Message| msg.chat.is_private() && msg.forward_origin().is_some())
+                .endpoint(collect_forwarded),
+        )
+        .branch(
+            dptree::filter(|msg: Message| msg.voice().is_some() || msg.audio().is_some())
+                .endpoint(handle_voice),
+        )
+        .branch(
+            dptree::filter(|msg: Message| vision_service::is_image(&msg)).endpoint(handle_photo),
+        )
+        .branch(
+            case![State::ReceiveFullName]
+                .endpoint(receive_full_name)
+                .branch(dptree::endpoint(invalid_state)),
+        )
+        .branch(case![State::HandleCrypto { message }].endpoint(receive_crypto_input))
+        .branch(
+            dptree::filter(|msg: Message| is_group(&msg))
+                .branch(dptree::filter(addressed_to_bot).endpoint(handle_group_mention))
+                .endpoint(ignore_group_chatter),
+        )
+        .branch(case![State::ChatWithAi].endpoint(handle_conversation));
+
+    // Buttons under a sent chart keep working whatever the dialogue state is
+    let chart_callback_handler = dptree::filter(|q: CallbackQuery| {
+        q.data
+            .as_deref()
+            .is_some_and(|data| data.starts_with(CHART_CALLBACK_PREFIX))
+    })
+    .endpoint(handle_chart_interval);
+
+    let alert_callback_handler = dptree::filter(|q: CallbackQuery| {
+        q.data
+            .as_deref()
+            .is_some_and(|data| data.starts_with(ALERT_DELETE_PREFIX))
+    })
+    .endpoint(delete_alert_button);
+
+    let book_callback_handler = dptree::filter(|q: CallbackQuery| {
+        q.data
+            .as_deref()
+            .is_some_and(|data| data.starts_with(BOOK_CALLBACK_PREFIX))
+    })
+    .endpoint(refresh_order_book);
 
     let callback_query_handler = Update::filter_callback_query()
+        .branch(chart_callback_handler)
+        .branch(alert_callback_handler)
+        .branch(book_callback_handler)
         .branch(case![State::HandleConversation { message }].endpoint(handle_prompt))
         .branch(case![State::HandleSoccer { message }].endpoint(handle_soccer))
         .branch(case![State::HandleCrypto { message }].endpoint(handle_crypto))
         .branch(case![State::HandleMovie { message }].endpoint(handle_movie));
 
-    dialogue::enter::<Update, InMemStorage<State>, State, _>()
-        .branch(message_handler)
-        .branch(callback_query_handler)
-        .branch(dptree::endpoint(handle_unknown_update))
+    // Inline queries carry no chat, so they are answered outside the dialogue
+    let inline_query_handler = Update::filter_inline_query().endpoint(inline_movie_search);
+
+    dptree::entry().branch(inline_query_handler).branch(
+        dialogue::enter::<Update, InMemStorage<State>, State, _>()
+            .branch(message_handler)
+            .branch(callback_query_handler)
+            .branch(dptree::endpoint(handle_unknown_update)),
+    )
 }
 
 pub async fn start(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
@@ -105,6 +228,575 @@ pub async fn cancel(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResu
     Ok(())
 }
 
+pub async fn set_adult(bot: Bot, msg: Message, value: String) -> HandlerResult {
+    if !can_change_settings(&bot, &msg).await? {
+        bot.send_message(
+            msg.chat.id,
+            "Only group admins can change the content settings.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let allow_adult = match value.trim().to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            let current = settings_service::get(msg.chat.id);
+            let state = if current.allow_adult { "on" } else { "off" };
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "Adult titles are currently {state}. Use /adult on or /adult off to change it."
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    settings_service::update(msg.chat.id, |settings| settings.allow_adult = allow_adult)?;
+
+    let reply = if allow_adult {
+        "Adult titles will now be shown in this chat."
+    } else {
+        "Adult titles will now be hidden in this chat."
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn set_language(bot: Bot, msg: Message, value: String) -> HandlerResult {
+    let value = value.trim();
+
+    if !value.is_empty() && !can_change_settings(&bot, &msg).await? {
+        bot.send_message(msg.chat.id, "Only group admins can change the language.")
+            .await?;
+        return Ok(());
+    }
+
+    let language = match value {
+        "" => {
+            let current = settings_service::get(msg.chat.id);
+            let language = current.language.as_deref().unwrap_or("auto");
+            bot.send_message(
+                msg.chat.id,
+                format!("Movie language is currently {language}. Use /language de, /language pt-BR or /language auto."),
+            )
+            .await?;
+            return Ok(());
+        }
+        "auto" => None,
+        tag if is_valid_language(tag) => Some(tag.to_string()),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "Please use a language code such as en, de or pt-BR.",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    settings_service::update(msg.chat.id, |settings| settings.language = language.clone())?;
+
+    let reply = match language {
+        Some(language) => format!("Movie results will now be shown in {language}."),
+        None => "Movie results will now follow your Telegram language.".to_string(),
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn set_persona(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let args = args.trim();
+
+    if args.is_empty() {
+        bot.send_message(msg.chat.id, persona_service::describe(msg.chat.id))
+            .await?;
+        return Ok(());
+    }
+
+    if !can_change_settings(&bot, &msg).await? {
+        bot.send_message(msg.chat.id, "Only group admins can change the AI persona.")
+            .await?;
+        return Ok(());
+    }
+
+    let (action, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let rest = rest.trim();
+
+    let (choice, reply) = match action.to_lowercase().as_str() {
+        "default" | "off" | "reset" => {
+            (None, "The AI is back to its default behaviour.".to_string())
+        }
+        "custom" if rest.is_empty() => {
+            bot.send_message(
+                msg.chat.id,
+                "Add the instructions, e.g. /persona custom Answer like a sports commentator",
+            )
+            .await?;
+            return Ok(());
+        }
+        "custom" if rest.chars().count() > persona_service::MAX_CUSTOM_PROMPT => {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "Please keep custom personas under {} characters.",
+                    persona_service::MAX_CUSTOM_PROMPT
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        "custom" => (
+            Some(PersonaChoice::Custom(rest.to_string())),
+            "Custom persona saved.".to_string(),
+        ),
+        id => match persona_service::find(id) {
+            Some(persona) => (
+                Some(PersonaChoice::Preset(persona.id.clone())),
+                format!("The AI is now the {}.", persona.name.to_lowercase()),
+            ),
+            None => {
+                bot.send_message(
+                    msg.chat.id,
+                    format!(
+                        "Unknown persona \"{id}\".\n\n{}",
+                        persona_service::describe(msg.chat.id)
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    persona_service::set(msg.chat.id, choice)?;
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn manage_context(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let words: Vec<String> = args
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    if words.is_empty() {
+        bot.send_message(msg.chat.id, gpt_service::describe_context(msg.chat.id))
+            .await?;
+        return Ok(());
+    }
+
+    if !can_change_settings(&bot, &msg).await? {
+        bot.send_message(
+            msg.chat.id,
+            "Only group admins can change the AI context settings.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let reply = match words
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [policy @ ("summarize" | "truncate")] => {
+            let policy = if *policy == "summarize" {
+                ContextPolicy::Summarize
+            } else {
+                ContextPolicy::Truncate
+            };
+            settings_service::update(msg.chat.id, |settings| settings.context.policy = policy)?;
+            format!("Context policy set to {policy}.")
+        }
+        ["budget", budget] => match budget.parse::<usize>() {
+            Ok(budget) if (MIN_CONTEXT_BUDGET..=MAX_CONTEXT_BUDGET).contains(&budget) => {
+                settings_service::update(msg.chat.id, |settings| settings.context.budget = budget)?;
+                format!("Context budget set to {budget} tokens.")
+            }
+            _ => format!(
+                "The budget must be between {MIN_CONTEXT_BUDGET} and {MAX_CONTEXT_BUDGET} tokens."
+            ),
+        },
+        _ => "Usage: /context, /context summarize, /context truncate or /context budget 4000"
+            .to_string(),
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+// Anyone can see their usage, chat admins may lower the limits and bot admins may also raise them
+pub async fn show_usage(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let words: Vec<String> = args
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    if words.is_empty() {
+        bot.send_message(msg.chat.id, usage_service::describe(user.id, msg.chat.id))
+            .await?;
+        return Ok(());
+    }
+
+    let usage_hint = "Usage: /usage, /usage limit daily <tokens>, /usage limit monthly <tokens> or /usage limit default";
+    let bot_admin = is_bot_admin(user.id);
+    if !bot_admin && !can_change_settings(&bot, &msg).await? {
+        bot.send_message(msg.chat.id, "Only group admins can change the AI limits.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut limits = settings_service::get(msg.chat.id).quota;
+    match words
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["limit", "default"] => limits = QuotaLimits::default(),
+        ["limit", period @ ("daily" | "monthly"), tokens] => {
+            let Ok(tokens) = tokens.replace(',', "").parse::<u64>() else {
+                bot.send_message(msg.chat.id, usage_hint).await?;
+                return Ok(());
+            };
+            let (slot, ceiling) = if *period == "daily" {
+                (&mut limits.daily_tokens, DEFAULT_DAILY_TOKENS)
+            } else {
+                (&mut limits.monthly_tokens, DEFAULT_MONTHLY_TOKENS)
+            };
+            if !bot_admin && tokens > ceiling {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Only the bot's operators can raise the {period} limit above {ceiling} tokens."),
+                )
+                .await?;
+                return Ok(());
+            }
+            *slot = tokens;
+        }
+        _ => {
+            bot.send_message(msg.chat.id, usage_hint).await?;
+            return Ok(());
+        }
+    }
+
+    let limits = usage_service::set_limits(msg.chat.id, limits)?;
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "AI limits for this chat: {} tokens a day and {} a month per user.",
+            limits.daily_tokens, limits.monthly_tokens
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn choose_model(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let requested = args.trim();
+    // Models the bot can't bill for are never offered
+    let available = llm_service::available_models().await.map(|models| {
+        models
+            .into_iter()
+            .filter(|model| usage_service::is_priced(model))
+            .collect::<Vec<_>>()
+    });
+    if let Err(err) = &available {
+        log::warn!("Failed to list the AI models: {}", err);
+    }
+
+    if requested.is_empty() {
+        let mut message = format!(
+            "AI model for this chat: {}\n",
+            llm_service::chat_model(msg.chat.id)
+        );
+        match &available {
+            Ok(models) if !models.is_empty() => {
+                message.push_str(&format!("\nAvailable: {}\n", models.join(", ")));
+            }
+            _ => message.push_str("\nThe list of available models couldn't be loaded.\n"),
+        }
+        message.push_str(
+            "\nChange it with /model <name>, /model default goes back to the bot's default.",
+        );
+        bot.send_message(msg.chat.id, message).await?;
+        return Ok(());
+    }
+
+    if !can_change_settings(&bot, &msg).await? {
+        bot.send_message(msg.chat.id, "Only group admins can change the AI model.")
+            .await?;
+        return Ok(());
+    }
+
+    let model = if requested.eq_ignore_ascii_case("default") {
+        None
+    } else {
+        let bot_admin = msg.from.as_ref().is_some_and(|user| is_bot_admin(user.id));
+        let refusal = match (llm_service::allowed_models(), &available) {
+            (Some(allowed), _) if !allowed.iter().any(|model| model == requested) => Some(format!(
+                "Unknown model \"{requested}\". Available: {}",
+                allowed.join(", ")
+            )),
+            (Some(_), _) => None,
+            (None, _) if !bot_admin => {
+                Some("Only the bot's admins can change the AI model.".to_string())
+            }
+            // Without a list to check against the server gets to reject unknown names itself
+            (None, Ok(models))
+                if !models.is_empty() && !models.iter().any(|model| model == requested) =>
+            {
+                Some(format!(
+                    "Unknown model \"{requested}\". Available: {}",
+                    models.join(", ")
+                ))
+            }
+            (None, _) => None,
+        };
+        let refusal = refusal.or_else(|| {
+            (!usage_service::is_priced(requested)).then(|| {
+                format!("The price of {requested} isn't known, so its usage couldn't be billed.")
+            })
+        });
+
+        if let Some(refusal) = refusal {
+            bot.send_message(msg.chat.id, refusal).await?;
+            return Ok(());
+        }
+        Some(requested.to_string())
+    };
+
+    settings_service::update(msg.chat.id, |settings| settings.model = model)?;
+    bot.send_message(
+        msg.chat.id,
+        format!("AI model set to {}.", llm_service::chat_model(msg.chat.id)),
+    )
+    .await?;
+    Ok(())
+}
+
+// Forwarded messages pile up until /summarize, only the first one is acknowledged to keep the chat quiet
+pub async fn collect_forwarded(bot: Bot, msg: Message) -> HandlerResult {
+    if summary_service::collect_forwarded(&msg) == 1 {
+        bot.send_message(
+            msg.chat.id,
+            "Got it. Forward as many messages as you like, then send /summarize.",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn summarize_chat(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let args = args.trim().to_lowercase();
+
+    if msg.chat.is_private() {
+        let messages = summary_service::forwarded(msg.chat.id);
+        if messages.is_empty() {
+            bot.send_message(
+                msg.chat.id,
+                "Forward me the messages you want summarized, then send /summarize.",
+            )
+            .await?;
+            return Ok(());
+        }
+        // The batch is kept for another try if the summary failed
+        if send_summary(
+            &bot,
+            user.id,
+            msg.chat.id,
+            &summary_service::transcript(&messages),
+        )
+        .await?
+        {
+            summary_service::clear_forwarded(msg.chat.id);
+        }
+        return Ok(());
+    }
+
+    if args == "on" || args == "off" {
+        if !can_change_settings(&bot, &msg).await? {
+            bot.send_message(
+                msg.chat.id,
+                "Only group admins can turn summaries on or off.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let enabled = args == "on";
+        summary_service::set_enabled(msg.chat.id, enabled)?;
+        let reply = if enabled {
+            "Summaries are on. I'll keep the last messages in memory from now on, /summarize turns them into a digest. \
+            If I don't seem to see messages, disable my privacy mode with @BotFather."
+        } else {
+            "Summaries are off and the collected messages are forgotten."
+        };
+        bot.send_message(msg.chat.id, reply).await?;
+        return Ok(());
+    }
+
+    if !settings_service::get(msg.chat.id).summaries {
+        bot.send_message(
+            msg.chat.id,
+            "Summaries are off in this group. An admin can turn them on with /summarize on, \
+            I'll only see messages sent after that.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let count = match args.as_str() {
+        "" => summary_service::DEFAULT_SUMMARY_MESSAGES,
+        count => match count.parse::<usize>() {
+            Ok(count) if count > 0 => count.min(summary_service::MAX_SUMMARY_MESSAGES),
+            _ => {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /summarize, /summarize 50, /summarize on or /summarize off",
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let messages = summary_service::recent(msg.chat.id, count);
+    if messages.is_empty() {
+        bot.send_message(msg.chat.id, "I haven't seen any messages to summarize yet.")
+            .await?;
+        return Ok(());
+    }
+
+    send_summary(
+        &bot,
+        user.id,
+        msg.chat.id,
+        &summary_service::transcript(&messages),
+    )
+    .await?;
+    Ok(())
+}
+
+// Returns whether a summary was produced
+async fn send_summary(
+    bot: &Bot,
+    user_id: UserId,
+    chat_id: ChatId,
+    transcript: &str,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let typing = keep_typing(bot.clone(), chat_id);
+    let result =
+        gpt_service::summarize_messages(&llm_service::provider(), user_id, chat_id, transcript)
+            .await;
+    typing.abort();
+
+    let (reply, summarized) = match result {
+        Ok(summary) if !summary.is_empty() => (summary, true),
+        Ok(_) => (
+            "The AI had nothing to say about those messages.".to_string(),
+            true,
+        ),
+        Err(AiError::QuotaExceeded(message) | AiError::Moderated(message)) => (message, false),
+        Err(err) => {
+            log::error!("Summarizing messages failed in {}: {}", chat_id, err);
+            (
+                "Sorry, I couldn't summarize the messages right now. Please try again later."
+                    .to_string(),
+                false,
+            )
+        }
+    };
+
+    for part in split_message(&reply, MESSAGE_LIMIT) {
+        bot.send_message(chat_id, part).await?;
+    }
+    Ok(summarized)
+}
+
+pub async fn search_movie(bot: Bot, msg: Message, query: String) -> HandlerResult {
+    if query.trim().is_empty() {
+        bot.send_message(msg.chat.id, "Please add a title, e.g. /movie Inception")
+            .await?;
+        return Ok(());
+    }
+
+    let settings = settings_service::get(msg.chat.id);
+    let client_language = msg
+        .from
+        .as_ref()
+        .and_then(|user| user.language_code.as_deref());
+    let language = settings.language_or(client_language);
+
+    match movie_service::search_movie(query.trim(), &settings, &language).await {
+        Ok(response) if response.is_empty() => {
+            bot.send_message(msg.chat.id, "No movies found for that title.")
+                .await?;
+        }
+        Ok(response) => {
+            for movie in response {
+                bot.send_message(msg.chat.id, movie).await?;
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to search movies: {}", err);
+            bot.send_message(
+                msg.chat.id,
+                "Sorry, I couldn't search for movies. Please try again later.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn inline_movie_search(bot: Bot, q: InlineQuery) -> HandlerResult {
+    let query = q.query.trim();
+
+    let results = if query.is_empty() {
+        Vec::new()
+    } else {
+        // Inline mode has no chat context, so the user's private chat settings apply
+        let settings = settings_service::get(ChatId::from(q.from.id));
+        let language = settings.language_or(q.from.language_code.as_deref());
+
+        movie_service::search_movie_results(query, &settings, &language)
+            .await?
+            .iter()
+            .take(20)
+            .enumerate()
+            .map(|(index, movie)| {
+                let content = InputMessageContent::Text(InputMessageContentText::new(
+                    movie_service::format_movie(index, movie),
+                ));
+
+                InlineQueryResult::Article(
+                    InlineQueryResultArticle::new(
+                        movie.id.to_string(),
+                        movie_service::display_title(movie),
+                        content,
+                    )
+                    .description(&movie.overview),
+                )
+            })
+            .collect()
+    };
+
+    bot.answer_inline_query(&q.id, results).await?;
+    Ok(())
+}
+
 pub async fn invalid_state(bot: Bot, msg: Message) -> HandlerResult {
     bot.send_message(
         msg.chat.id,
@@ -123,11 +815,14 @@ pub async fn receive_full_name(bot: Bot, dialogue: MyDialogue, msg: Message) ->
                 "Get Live Scores",
                 "Get latest crypto charts",
                 "top trending movies",
+                CHAT_WITH_AI,
             ]
             .map(|service| InlineKeyboardButton::callback(service, service));
 
             bot.send_message(msg.chat.id, "select a service:")
-                .reply_markup(InlineKeyboardMarkup::new([service]))
+                .reply_markup(InlineKeyboardMarkup::new(
+                    service.chunks(2).map(|row| row.to_vec()),
+                ))
                 .await?;
 
             dialogue
@@ -161,21 +856,47 @@ pub async fn handle_prompt(
     if let Some(service) = q.data {
         log::info!("this is the message {}", &service);
 
+        if service == CHAT_WITH_AI {
+            bot.answer_callback_query(&q.id).await?;
+            // Groups talk to the AI through mentions and replies instead of a dialogue state
+            if !dialogue.chat_id().is_user() {
+                bot.send_message(
+                    dialogue.chat_id(),
+                    "In groups, mention me or reply to one of my messages to talk to the AI.",
+                )
+                .await?;
+                return Ok(());
+            }
+            bot.send_message(
+                dialogue.chat_id(),
+                "You're chatting with the AI now. I remember this conversation, \
+                send /reset to start over or /cancel to leave.",
+            )
+            .await?;
+            dialogue.update(State::ChatWithAi).await?;
+            return Ok(());
+        }
+
         match handle_message(&service) {
             Ok(prompts) => {
-                let buttons = prompts
-                    .into_iter()
-                    .map(|service| InlineKeyboardButton::callback(service, service));
+                let buttons: Vec<Vec<InlineKeyboardButton>> = prompts
+                    .chunks(3)
+                    .map(|row| {
+                        row.iter()
+                            .map(|service| InlineKeyboardButton::callback(*service, *service))
+                            .collect()
+                    })
+                    .collect();
 
                 bot.answer_callback_query(&q.id).await?;
 
                 if let Some(message) = q.message {
                     bot.edit_message_text(message.chat().id, message.id(), "Select a service:")
-                        .reply_markup(InlineKeyboardMarkup::new([buttons]))
+                        .reply_markup(InlineKeyboardMarkup::new(buttons))
                         .await?;
                 } else {
                     bot.send_message(dialogue.chat_id(), "Select a service:")
-                        .reply_markup(InlineKeyboardMarkup::new([buttons]))
+                        .reply_markup(InlineKeyboardMarkup::new(buttons))
                         .await?;
                 }
 
@@ -255,11 +976,15 @@ pub async fn handle_movie(
 ) -> HandlerResult {
     if let Some(service) = q.data {
         log::info!("this is the message {}", &service);
+        let settings = settings_service::get(dialogue.chat_id());
+        let language = settings.language_or(q.from.language_code.as_deref());
 
         match service.as_str() {
-            "Top trending Movie" => match movie_service::trending_movie().await {
+            "Top trending Movie" => match movie_service::trending_movie(&settings, &language).await
+            {
                 Ok(response) => {
-                    bot.send_message(dialogue.chat_id(), "Trending Movies".to_owned()).await?;
+                    bot.send_message(dialogue.chat_id(), "Trending Movies".to_owned())
+                        .await?;
                     for movie in response {
                         bot.send_message(dialogue.chat_id(), movie).await?;
                     }
@@ -273,9 +998,10 @@ pub async fn handle_movie(
                     .await?;
                 }
             },
-            "Popular Movie" => match movie_service::popular_movie().await {
+            "Popular Movie" => match movie_service::popular_movie(&settings, &language).await {
                 Ok(response) => {
-                    bot.send_message(dialogue.chat_id(), "Popular Movies: ".to_owned()).await?;
+                    bot.send_message(dialogue.chat_id(), "Popular Movies: ".to_owned())
+                        .await?;
                     for movie in response {
                         bot.send_message(dialogue.chat_id(), movie).await?;
                     }
@@ -289,25 +1015,35 @@ pub async fn handle_movie(
                     .await?;
                 }
             },
-            "Movies in Theatres" => match movie_service::get_movies_in_theatres().await {
-                Ok(response) => {
-                    bot.send_message(dialogue.chat_id(), "Get a list of movies that are currently in theatres.: ".to_owned()).await?;
-                    for movie in response {
-                        bot.send_message(dialogue.chat_id(), movie).await?;
+            "Movies in Theatres" => {
+                match movie_service::get_movies_in_theatres(&settings, &language).await {
+                    Ok(response) => {
+                        bot.send_message(
+                            dialogue.chat_id(),
+                            "Get a list of movies that are currently in theatres.: ".to_owned(),
+                        )
+                        .await?;
+                        for movie in response {
+                            bot.send_message(dialogue.chat_id(), movie).await?;
+                        }
+                    }
+                    Err(err) => {
+                        log::error!("Failed to fetch today's events: {}", err);
+                        bot.send_message(
+                            dialogue.chat_id(),
+                            "Sorry, I couldn't fetch today's events. Please try again later.",
+                        )
+                        .await?;
                     }
                 }
-                Err(err) => {
-                    log::error!("Failed to fetch today's events: {}", err);
+            }
+            "Upcoming Movie" => match movie_service::upcoming_movie(&settings, &language).await {
+                Ok(response) => {
                     bot.send_message(
                         dialogue.chat_id(),
-                        "Sorry, I couldn't fetch today's events. Please try again later.",
+                        "Get a list of movies that will be released soon: ".to_owned(),
                     )
                     .await?;
-                }
-            },
-            "Upcoming Movie" => match movie_service::upcoming_movie().await {
-                Ok(response) => {
-                    bot.send_message(dialogue.chat_id(), "Get a list of movies that will be released soon: ".to_owned()).await?;
                     for movie in response {
                         bot.send_message(dialogue.chat_id(), movie).await?;
                     }
@@ -342,35 +1078,1300 @@ pub async fn handle_crypto(
 ) -> HandlerResult {
     if let Some(service) = q.data {
         log::info!("this is the message {}", &service);
+        bot.answer_callback_query(&q.id).await?;
+
+        match service.as_str() {
+            "Price lookup" | "Price chart" | "Order book" => {
+                bot.send_message(
+                    dialogue.chat_id(),
+                    "Send me a trading pair such as BTCUSDT, or just a coin like ETH.",
+                )
+                .await?;
+                dialogue
+                    .update(State::HandleCrypto { message: service })
+                    .await?;
+            }
+            "Price alerts" => {
+                let (text, keyboard) = alerts_overview(dialogue.chat_id());
+                bot.send_message(dialogue.chat_id(), format!("{text}\n\n{ALERT_HELP}"))
+                    .reply_markup(keyboard)
+                    .await?;
+                dialogue
+                    .update(State::HandleCrypto { message: service })
+                    .await?;
+            }
+            "Watchlist" => {
+                send_watchlist(&bot, dialogue.chat_id(), q.from.id).await?;
+                bot.send_message(
+                    dialogue.chat_id(),
+                    "Send me pairs to add to your watchlist, e.g. BTC ETH SOLUSDT",
+                )
+                .await?;
+                dialogue
+                    .update(State::HandleCrypto { message: service })
+                    .await?;
+            }
+            "Top movers" => {
+                send_top_movers(&bot, dialogue.chat_id()).await?;
+            }
+            "Convert" => {
+                bot.send_message(
+                    dialogue.chat_id(),
+                    "What should I convert? e.g. 0.5 BTC EUR",
+                )
+                .await?;
+                dialogue
+                    .update(State::HandleCrypto { message: service })
+                    .await?;
+            }
+            "What if" => {
+                bot.send_message(
+                    dialogue.chat_id(),
+                    "Send me a pair, start date, amount and frequency, e.g. BTC 2022-01-01 100 weekly",
+                )
+                .await?;
+                dialogue
+                    .update(State::HandleCrypto { message: service })
+                    .await?;
+            }
+            _ => {
+                bot.send_message(
+                    dialogue.chat_id(),
+                    "Sorry, I don't recognize that command. Please choose a valid option.",
+                )
+                .await?;
+            }
+        }
     }
     Ok(())
 }
 
-async fn handle_unknown_update(update: Update) -> HandlerResult {
-    log::warn!("Received unknown update: {:?}", update);
-    Ok(())
-}
+// Free text while in the crypto menu, interpreted according to the last chosen option
+pub async fn receive_crypto_input(bot: Bot, message: String, msg: Message) -> HandlerResult {
+    let Some(text) = msg.text() else {
+        bot.send_message(msg.chat.id, "Please send the trading pair as text.")
+            .await?;
+        return Ok(());
+    };
 
-#[warn(unused_variables)]
-pub async fn handle_conversation(
-    bot: Bot,
-    dialogue: MyDialogue,
-    message: String,
-    q: CallbackQuery,
-) -> HandlerResult {
-    if let Some(service) = q.data {
-        match gpt_quick_reply(&service).await {
-            Ok(conversation_reply) => {
-                bot.answer_callback_query(&q.id).await?;
-                bot.send_message(dialogue.chat_id(), conversation_reply)
-                    .await?;
+    match message.as_str() {
+        "Price lookup" => match crypto_service::price_summary(text).await {
+            Ok(summary) => {
+                bot.send_message(msg.chat.id, summary).await?;
             }
-            Err(e) => {
-                dialogue.exit().await?;
-                bot.answer_callback_query(&q.id).await?;
-                eprintln!("something went wrong : {}", e);
+            Err(err) => {
+                log::error!("Failed to fetch price for {}: {}", text, err);
+                bot.send_message(
+                    msg.chat.id,
+                    "Sorry, I couldn't find a price for that pair. Please check the symbol and try again.",
+                )
+                .await?;
             }
-        }
-    }
+        },
+        "Price chart" => {
+            let chart = match crypto_service::normalize_symbol(text) {
+                Ok(symbol) => {
+                    crypto_service::price_chart(&symbol, crypto_service::DEFAULT_CHART_INTERVAL)
+                        .await
+                        .map(|png| (symbol, png))
+                }
+                Err(err) => Err(err),
+            };
+
+            match chart {
+                Ok((symbol, png)) => {
+                    bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("chart.png"))
+                        .caption(format!(
+                            "{symbol} {}",
+                            crypto_service::DEFAULT_CHART_INTERVAL
+                        ))
+                        .reply_markup(chart_keyboard(&symbol))
+                        .await?;
+                }
+                Err(err) => {
+                    log::error!("Failed to draw chart for {}: {}", text, err);
+                    bot.send_message(
+                        msg.chat.id,
+                        "Sorry, I couldn't draw a chart for that pair. Please check the symbol and try again.",
+                    )
+                    .await?;
+                }
+            }
+        }
+        "Price alerts" => {
+            let reply = create_alert(msg.chat.id, text).await;
+            bot.send_message(msg.chat.id, reply).await?;
+        }
+        "Watchlist" => {
+            if let Some(user) = msg.from.as_ref() {
+                add_to_watchlist(&bot, msg.chat.id, user.id, text).await?;
+            }
+        }
+        "Convert" => {
+            send_conversion(&bot, msg.chat.id, text).await?;
+        }
+        "Order book" => {
+            send_order_book(&bot, msg.chat.id, text).await?;
+        }
+        "What if" => {
+            send_backtest(&bot, msg.chat.id, text).await?;
+        }
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "Please pick an option from the crypto menu first.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn create_alert(chat_id: ChatId, text: &str) -> String {
+    let result = match alert_service::parse_alert(text) {
+        Ok((symbol, condition)) => alert_service::add(chat_id, symbol, condition).await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(alert) => format!("Alert created: {}", alert_service::format_alert(&alert)),
+        Err(err) => {
+            log::warn!("Failed to create alert from {:?}: {}", text, err);
+            format!("{err}\n\n{ALERT_HELP}")
+        }
+    }
+}
+
+fn alerts_overview(chat_id: ChatId) -> (String, InlineKeyboardMarkup) {
+    let alerts = alert_service::list(chat_id);
+
+    if alerts.is_empty() {
+        return (
+            "You have no price alerts.".to_string(),
+            InlineKeyboardMarkup::default(),
+        );
+    }
+
+    let mut text = String::from("Your price alerts:\n");
+    let mut buttons = Vec::new();
+
+    for alert in &alerts {
+        text.push_str(&format!("{}\n", alert_service::format_alert(alert)));
+        buttons.push([InlineKeyboardButton::callback(
+            format!("Delete #{}", alert.id),
+            format!("{ALERT_DELETE_PREFIX}{}", alert.id),
+        )]);
+    }
+
+    (text, InlineKeyboardMarkup::new(buttons))
+}
+
+pub async fn manage_alerts(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let args = args.trim();
+    let mut words = args.split_whitespace();
+
+    match words.next().map(|word| word.to_lowercase()).as_deref() {
+        None => {
+            bot.send_message(msg.chat.id, ALERT_HELP).await?;
+        }
+        Some("list") => {
+            let (text, keyboard) = alerts_overview(msg.chat.id);
+            bot.send_message(msg.chat.id, text)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Some("delete") | Some("remove") => {
+            let reply = match words
+                .next()
+                .map(|id| id.trim_start_matches('#').parse::<u64>())
+            {
+                Some(Ok(id)) => match alert_service::delete(msg.chat.id, id) {
+                    Ok(()) => format!("Alert #{id} deleted."),
+                    Err(err) => err.to_string(),
+                },
+                _ => "Please add the alert id, e.g. /alert delete 3".to_string(),
+            };
+            bot.send_message(msg.chat.id, reply).await?;
+        }
+        Some(_) => {
+            let reply = create_alert(msg.chat.id, args).await;
+            bot.send_message(msg.chat.id, reply).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send_watchlist(bot: &Bot, chat_id: ChatId, user_id: UserId) -> HandlerResult {
+    match watchlist_service::table(user_id).await {
+        Ok(table) => {
+            bot.send_message(chat_id, table)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to fetch watchlist for {}: {}", user_id, err);
+            bot.send_message(
+                chat_id,
+                "Sorry, I couldn't fetch your watchlist prices. Please try again later.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send_top_movers(bot: &Bot, chat_id: ChatId) -> HandlerResult {
+    match watchlist_service::top_movers_table().await {
+        Ok(table) => {
+            bot.send_message(chat_id, table)
+                .parse_mode(ParseMode::Html)
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to fetch top movers: {}", err);
+            bot.send_message(
+                chat_id,
+                "Sorry, I couldn't fetch the market overview. Please try again later.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send_conversion(bot: &Bot, chat_id: ChatId, text: &str) -> HandlerResult {
+    let reply = match conversion_service::convert(text).await {
+        Ok(conversion) => conversion_service::format_conversion(&conversion),
+        Err(err) => {
+            log::warn!("Failed to convert {:?}: {}", text, err);
+            format!("{err}\n\nUsage: /convert 0.5 BTC EUR")
+        }
+    };
+
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+pub async fn convert_currency(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    send_conversion(&bot, msg.chat.id, &args).await
+}
+
+async fn send_backtest(bot: &Bot, chat_id: ChatId, text: &str) -> HandlerResult {
+    let request = match backtest_service::parse_backtest(text) {
+        Ok(request) => request,
+        Err(err) => {
+            bot.send_message(
+                chat_id,
+                format!("{err}\n\n{}", backtest_service::BACKTEST_USAGE),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let result = match backtest_service::run(request).await {
+        Ok(result) => result,
+        Err(err) => {
+            log::error!("Failed to backtest {:?}: {}", text, err);
+            bot.send_message(
+                chat_id,
+                "Sorry, I couldn't load the price history for that backtest. Please check the pair and date.",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let summary = backtest_service::format_result(&result);
+    match backtest_service::equity_chart(&result).await {
+        Ok(png) => {
+            bot.send_photo(chat_id, InputFile::memory(png).file_name("backtest.png"))
+                .caption(summary)
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to draw equity curve: {}", err);
+            bot.send_message(chat_id, summary).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn run_backtest(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    send_backtest(&bot, msg.chat.id, &args).await
+}
+
+fn order_book_keyboard(symbol: &str, levels: usize) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        "Refresh",
+        format!("{BOOK_CALLBACK_PREFIX}{symbol}:{levels}"),
+    )]])
+}
+
+async fn order_book_text(
+    symbol: &str,
+    levels: usize,
+) -> Result<String, crate::models::assets::CryptoError> {
+    let depth = crypto_service::market_depth(symbol, levels).await?;
+    Ok(format!(
+        "<b>{symbol} order book</b>\n<pre>{}</pre>\nUpdated {}",
+        crypto_service::format_market_depth(&depth),
+        chrono::Utc::now().format("%H:%M:%S UTC")
+    ))
+}
+
+// "BTCUSDT" or "BTCUSDT 20"
+async fn send_order_book(bot: &Bot, chat_id: ChatId, text: &str) -> HandlerResult {
+    let mut words = text.split_whitespace();
+    let levels = words
+        .clone()
+        .nth(1)
+        .and_then(|levels| levels.parse::<usize>().ok())
+        .unwrap_or(crypto_service::DEFAULT_DEPTH)
+        .clamp(1, MAX_BOOK_LEVELS);
+
+    let symbol = match words.next().map(crypto_service::normalize_symbol) {
+        Some(Ok(symbol)) => symbol,
+        _ => {
+            bot.send_message(chat_id, "Please add a trading pair, e.g. /book BTCUSDT 10")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match order_book_text(&symbol, levels).await {
+        Ok(text) => {
+            bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(order_book_keyboard(&symbol, levels))
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to fetch order book for {}: {}", symbol, err);
+            bot.send_message(
+                chat_id,
+                "Sorry, I couldn't fetch the order book for that pair. Please check the symbol and try again.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn show_order_book(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    send_order_book(&bot, msg.chat.id, &args).await
+}
+
+pub async fn refresh_order_book(bot: Bot, q: CallbackQuery) -> HandlerResult {
+    let target = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(BOOK_CALLBACK_PREFIX))
+        .and_then(|data| data.split_once(':'))
+        .and_then(|(symbol, levels)| Some((symbol.to_string(), levels.parse::<usize>().ok()?)));
+
+    let (Some((symbol, levels)), Some(message)) = (target, q.message.as_ref()) else {
+        bot.answer_callback_query(&q.id).await?;
+        return Ok(());
+    };
+
+    match order_book_text(&symbol, levels).await {
+        Ok(text) => {
+            bot.answer_callback_query(&q.id).await?;
+            bot.edit_message_text(message.chat().id, message.id(), text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(order_book_keyboard(&symbol, levels))
+                .await?;
+        }
+        Err(err) => {
+            log::error!("Failed to refresh order book for {}: {}", symbol, err);
+            bot.answer_callback_query(&q.id)
+                .text("Sorry, I couldn't refresh the order book. Please try again later.")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+fn split_pairs(text: &str) -> Vec<&str> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+async fn add_to_watchlist(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: UserId,
+    text: &str,
+) -> HandlerResult {
+    let inputs = split_pairs(text);
+
+    if inputs.is_empty() {
+        bot.send_message(chat_id, "Please add at least one pair, e.g. /watch BTC ETH")
+            .await?;
+        return Ok(());
+    }
+
+    match watchlist_service::add(user_id, &inputs).await {
+        Ok((added, rejected)) => {
+            let mut reply = String::new();
+            if !added.is_empty() {
+                reply.push_str(&format!("Added to your watchlist: {}\n", added.join(", ")));
+            }
+            if !rejected.is_empty() {
+                reply.push_str(&format!(
+                    "Skipped (unknown pair or watchlist full, max {}): {}\n",
+                    watchlist_service::MAX_WATCHLIST_SIZE,
+                    rejected.join(", ")
+                ));
+            }
+            if reply.is_empty() {
+                reply.push_str("Those pairs are already on your watchlist.");
+            }
+            bot.send_message(chat_id, reply).await?;
+            send_watchlist(bot, chat_id, user_id).await?;
+        }
+        Err(err) => {
+            log::error!("Failed to update watchlist for {}: {}", user_id, err);
+            bot.send_message(
+                chat_id,
+                "Sorry, I couldn't update your watchlist. Please try again later.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn watch_pairs(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    if let Some(user) = msg.from.as_ref() {
+        add_to_watchlist(&bot, msg.chat.id, user.id, &args).await?;
+    }
+    Ok(())
+}
+
+pub async fn unwatch_pairs(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let inputs = split_pairs(&args);
+    let removed = watchlist_service::remove(user.id, &inputs)?;
+
+    let reply = if removed.is_empty() {
+        "None of those pairs were on your watchlist.".to_string()
+    } else {
+        format!("Removed from your watchlist: {}", removed.join(", "))
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn show_watchlist(bot: Bot, msg: Message) -> HandlerResult {
+    if let Some(user) = msg.from.as_ref() {
+        send_watchlist(&bot, msg.chat.id, user.id).await?;
+    }
+    Ok(())
+}
+
+pub async fn show_top_movers(bot: Bot, msg: Message) -> HandlerResult {
+    send_top_movers(&bot, msg.chat.id).await
+}
+
+async fn record_trade(bot: Bot, msg: Message, side: TradeSide, args: String) -> HandlerResult {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let reply = match portfolio_service::record(user.id, side, &args).await {
+        Ok(trade) => format!(
+            "Recorded {} of {} {} at {} on {}.",
+            trade.side,
+            trade.quantity.normalize(),
+            trade.symbol,
+            trade.price.normalize(),
+            trade.date
+        ),
+        Err(err) => {
+            log::warn!("Failed to record {} from {:?}: {}", side, args, err);
+            format!("{err}\n\nUsage: /{side} BTCUSDT 0.5 [price] [YYYY-MM-DD]")
+        }
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn record_buy(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    record_trade(bot, msg, TradeSide::Buy, args).await
+}
+
+pub async fn record_sell(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    record_trade(bot, msg, TradeSide::Sell, args).await
+}
+
+pub async fn show_portfolio(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    if args.trim().eq_ignore_ascii_case("export") {
+        let csv = portfolio_service::export_csv(user.id);
+        bot.send_document(
+            msg.chat.id,
+            InputFile::memory(csv).file_name("portfolio.csv"),
+        )
+        .caption("Your trade ledger")
+        .await?;
+        return Ok(());
+    }
+
+    match portfolio_service::summary(user.id).await {
+        Ok(summary) => {
+            bot.send_message(msg.chat.id, summary).await?;
+        }
+        Err(err) => {
+            log::error!("Failed to build portfolio for {}: {}", user.id, err);
+            bot.send_message(
+                msg.chat.id,
+                "Sorry, I couldn't value your portfolio. Please try again later.",
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn paper_trade(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let name = user.full_name();
+
+    let (action, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+
+    let reply = match action.to_lowercase().as_str() {
+        "" | "account" | "orders" => match paper_service::account_summary(user.id, &name).await {
+            Ok(summary) => summary,
+            Err(err) => {
+                log::error!("Failed to value paper account for {}: {}", user.id, err);
+                "Sorry, I couldn't value your paper account. Please try again later.".to_string()
+            }
+        },
+        "buy" | "sell" => {
+            let side = if action.eq_ignore_ascii_case("buy") {
+                TradeSide::Buy
+            } else {
+                TradeSide::Sell
+            };
+            match paper_service::place_order(user.id, &name, msg.chat.id, side, rest).await {
+                Ok(reply) => reply,
+                Err(err) => {
+                    log::warn!("Failed to place paper {} from {:?}: {}", side, rest, err);
+                    format!("{err}\n\nUsage: /paper {side} BTCUSDT 0.01 [limit price]")
+                }
+            }
+        }
+        "cancel" => match rest.trim().trim_start_matches('#').parse::<u64>() {
+            Ok(id) => match paper_service::cancel_order(user.id, id) {
+                Ok(()) => format!("Paper order #{id} cancelled."),
+                Err(err) => err.to_string(),
+            },
+            Err(_) => "Usage: /paper cancel <order id>".to_string(),
+        },
+        "history" => paper_service::history(user.id),
+        "reset" => match paper_service::reset(user.id, &name) {
+            Ok(()) => "Your paper account was reset to 10,000 USDT.".to_string(),
+            Err(err) => {
+                log::error!("Failed to reset paper account for {}: {}", user.id, err);
+                "Sorry, I couldn't reset your paper account.".to_string()
+            }
+        },
+        _ => "Usage: /paper [buy|sell <pair> <quantity> [limit]] [orders] [cancel <id>] [history] [reset]"
+            .to_string(),
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn show_leaderboard(bot: Bot, msg: Message) -> HandlerResult {
+    if msg.chat.is_private() {
+        bot.send_message(
+            msg.chat.id,
+            "The leaderboard ranks paper traders in a group chat.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let reply = match paper_service::leaderboard(msg.chat.id).await {
+        Ok(leaderboard) => leaderboard,
+        Err(err) => {
+            log::error!("Failed to build leaderboard for {}: {}", msg.chat.id, err);
+            "Sorry, I couldn't build the leaderboard. Please try again later.".to_string()
+        }
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn technical_analysis(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let words: Vec<String> = args
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+    let with_chart = words.iter().any(|word| word == "chart");
+    let mut rest = words.iter().filter(|word| *word != "chart");
+
+    let Some(symbol) = rest.next() else {
+        bot.send_message(msg.chat.id, TA_USAGE).await?;
+        return Ok(());
+    };
+    let interval = rest
+        .next()
+        .map(String::as_str)
+        .unwrap_or(crypto_service::DEFAULT_CHART_INTERVAL);
+
+    let result = match crypto_service::normalize_symbol(symbol) {
+        Ok(symbol) => indicator_service::analyze(&symbol, interval).await,
+        Err(err) => Err(err),
+    };
+
+    let (analysis, overlays, candles) = match result {
+        Ok(result) => result,
+        Err(
+            err @ (CryptoError::InvalidSymbol(_)
+            | CryptoError::InvalidInterval(_)
+            | CryptoError::NotEnoughHistory(_)),
+        ) => {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "{err}\n\n{TA_USAGE}\nIntervals: {}",
+                    crypto_service::CHART_INTERVALS.join(", ")
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err) => {
+            log::error!("Failed to analyze {}: {}", args, err);
+            bot.send_message(
+                msg.chat.id,
+                "Sorry, I couldn't load the price history for that pair. Please try again later.",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if with_chart {
+        match indicator_service::analysis_chart(candles, overlays, &analysis).await {
+            Ok(png) => {
+                bot.send_photo(
+                    msg.chat.id,
+                    InputFile::memory(png).file_name("analysis.png"),
+                )
+                .caption(format!(
+                    "{} {} with SMAs and Bollinger bands",
+                    analysis.symbol, analysis.interval
+                ))
+                .await?;
+            }
+            Err(err) => log::error!(
+                "Failed to draw analysis chart for {}: {}",
+                analysis.symbol,
+                err
+            ),
+        }
+    }
+
+    bot.send_message(msg.chat.id, indicator_service::format_analysis(&analysis))
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_alert_button(bot: Bot, q: CallbackQuery) -> HandlerResult {
+    let id = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(ALERT_DELETE_PREFIX))
+        .and_then(|id| id.parse::<u64>().ok());
+
+    let (Some(id), Some(message)) = (id, q.message.as_ref()) else {
+        bot.answer_callback_query(&q.id).await?;
+        return Ok(());
+    };
+
+    let chat_id = message.chat().id;
+    let answer = match alert_service::delete(chat_id, id) {
+        Ok(()) => format!("Alert #{id} deleted."),
+        Err(err) => err.to_string(),
+    };
+    bot.answer_callback_query(&q.id).text(answer).await?;
+
+    let (text, keyboard) = alerts_overview(chat_id);
+    bot.edit_message_text(chat_id, message.id(), text)
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+fn chart_keyboard(symbol: &str) -> InlineKeyboardMarkup {
+    let buttons = crypto_service::CHART_INTERVALS.map(|interval| {
+        InlineKeyboardButton::callback(
+            interval,
+            format!("{CHART_CALLBACK_PREFIX}{symbol}:{interval}"),
+        )
+    });
+    InlineKeyboardMarkup::new([buttons])
+}
+
+// Re-render the chart in place when one of the interval buttons is pressed
+pub async fn handle_chart_interval(bot: Bot, q: CallbackQuery) -> HandlerResult {
+    let Some((symbol, interval)) = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(CHART_CALLBACK_PREFIX))
+        .and_then(|data| data.split_once(':'))
+    else {
+        bot.answer_callback_query(&q.id).await?;
+        return Ok(());
+    };
+
+    let Some(message) = q.message.as_ref() else {
+        bot.answer_callback_query(&q.id).await?;
+        return Ok(());
+    };
+
+    match crypto_service::price_chart(symbol, interval).await {
+        Ok(png) => {
+            bot.answer_callback_query(&q.id).await?;
+
+            let media = InputMedia::Photo(
+                InputMediaPhoto::new(InputFile::memory(png).file_name("chart.png"))
+                    .caption(format!("{symbol} {interval}")),
+            );
+
+            bot.edit_message_media(message.chat().id, message.id(), media)
+                .reply_markup(chart_keyboard(symbol))
+                .await?;
+        }
+        Err(err) => {
+            log::error!(
+                "Failed to redraw chart for {} {}: {}",
+                symbol,
+                interval,
+                err
+            );
+            bot.answer_callback_query(&q.id)
+                .text("Sorry, I couldn't update the chart. Please try again later.")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_unknown_update(update: Update) -> HandlerResult {
+    log::warn!("Received unknown update: {:?}", update);
+    Ok(())
+}
+
+// Redraws the placeholder with the reply so far, returns the text last shown
+async fn progressive_edits(
+    bot: Bot,
+    chat_id: ChatId,
+    message_id: MessageId,
+    mut partial: watch::Receiver<String>,
+) -> String {
+    let mut shown = String::new();
+    if !moderation_service::streams_partials() {
+        return shown;
+    }
+
+    while partial.changed().await.is_ok() {
+        let screened = moderation_service::screen_partial(&partial.borrow_and_update());
+        let text: String = screened.chars().take(MESSAGE_LIMIT).collect();
+
+        if !text.trim().is_empty() && text != shown {
+            match bot.edit_message_text(chat_id, message_id, &text).await {
+                Ok(_) => shown = text,
+                Err(err) => log::warn!("Failed to update streamed reply in {}: {}", chat_id, err),
+            }
+        }
+
+        tokio::time::sleep(STREAM_EDIT_INTERVAL).await;
+    }
+
+    shown
+}
+
+// Sends a placeholder and edits it as the answer streams in, overflow goes into follow-up messages.
+// Returns the message holding the start of the answer together with the whole answer
+async fn send_ai_reply(
+    bot: &Bot,
+    user_id: UserId,
+    chat_id: ChatId,
+    text: &str,
+    reply_to: Option<MessageId>,
+    thread: &[ChatTurn],
+) -> Result<(MessageId, String), Box<dyn std::error::Error + Send + Sync>> {
+    let typing = keep_typing(bot.clone(), chat_id);
+    let mut request = bot.send_message(chat_id, "…");
+    if let Some(reply_to) = reply_to {
+        request =
+            request.reply_parameters(ReplyParameters::new(reply_to).allow_sending_without_reply());
+    }
+    let placeholder = match request.await {
+        Ok(placeholder) => placeholder,
+        Err(err) => {
+            typing.abort();
+            return Err(err.into());
+        }
+    };
+
+    let (sender, receiver) = watch::channel(String::new());
+    let editor = task::spawn(progressive_edits(
+        bot.clone(),
+        chat_id,
+        placeholder.id,
+        receiver,
+    ));
+
+    let provider = llm_service::provider();
+    let result =
+        gpt_service::maintain_conversation(&provider, user_id, chat_id, text, thread, sender).await;
+    typing.abort();
+    let shown = editor.await.unwrap_or_default();
+
+    let reply = match result {
+        Ok(reply) if !reply.trim().is_empty() => reply,
+        Ok(_) => "The AI had nothing to say to that.".to_string(),
+        Err(AiError::QuotaExceeded(message) | AiError::Moderated(message)) => message,
+        Err(err) => {
+            log::error!("AI conversation failed in {}: {}", chat_id, err);
+            "Sorry, the AI couldn't answer right now. Please try again later.".to_string()
+        }
+    };
+
+    let mut parts = split_message(&reply, MESSAGE_LIMIT).into_iter();
+    if let Some(first) = parts.next().filter(|first| *first != shown) {
+        bot.edit_message_text(chat_id, placeholder.id, first)
+            .await?;
+    }
+    for part in parts {
+        bot.send_message(chat_id, part).await?;
+    }
+    Ok((placeholder.id, reply))
+}
+
+// Free text in AI mode goes to the model together with the chat's history
+pub async fn handle_conversation(bot: Bot, msg: Message, source: InputSource) -> HandlerResult {
+    let Some(text) = msg.text() else {
+        bot.send_message(msg.chat.id, "I can only chat about text messages for now.")
+            .await?;
+        return Ok(());
+    };
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let (_, reply) = send_ai_reply(&bot, user.id, msg.chat.id, text, None, &[]).await?;
+    if source == InputSource::Voice {
+        send_voice_reply(&bot, user.id, msg.chat.id, &reply, msg.id).await;
+    }
+    Ok(())
+}
+
+fn is_group(msg: &Message) -> bool {
+    msg.chat.is_group() || msg.chat.is_supergroup()
+}
+
+fn mentions_bot(msg: &Message, me: &Me) -> bool {
+    let mention = format!("@{}", me.username());
+
+    let entities = msg
+        .parse_entities()
+        .or_else(|| msg.parse_caption_entities())
+        .unwrap_or_default();
+
+    entities.iter().any(|entity| match entity.kind() {
+        MessageEntityKind::Mention => entity.text().eq_ignore_ascii_case(&mention),
+        MessageEntityKind::TextMention { user } => user.id == me.id,
+        _ => false,
+    })
+}
+
+fn replies_to_bot(msg: &Message, me: &Me) -> bool {
+    msg.reply_to_message()
+        .and_then(|parent| parent.from.as_ref())
+        .is_some_and(|author| author.id == me.id)
+}
+
+// In groups the AI only answers when mentioned or replied to, everything else is ordinary chatter
+fn addressed_to_bot(msg: Message, me: Me) -> bool {
+    mentions_bot(&msg, &me) || replies_to_bot(&msg, &me)
+}
+
+fn without_mention(text: &str, me: &Me) -> String {
+    let mention = format!("@{}", me.username()).to_lowercase();
+
+    text.split_whitespace()
+        .filter(|word| {
+            word.trim_end_matches([',', ':', '!', '?', '.'])
+                .to_lowercase()
+                != mention
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Group members are told apart by name, the bot's own messages are the assistant's turns
+fn thread_turn(msg: &Message, me: &Me) -> Option<ChatTurn> {
+    let text = msg.text().or_else(|| msg.caption())?;
+    let author = msg.from.as_ref()?;
+
+    Some(if author.id == me.id {
+        ChatTurn::new(ChatRole::Assistant, text)
+    } else {
+        ChatTurn::new(
+            ChatRole::User,
+            &format!("{}: {}", author.first_name, without_mention(text, me)),
+        )
+    })
+}
+
+pub async fn handle_group_mention(
+    bot: Bot,
+    msg: Message,
+    me: Me,
+    source: InputSource,
+) -> HandlerResult {
+    let (Some(text), Some(user)) = (msg.text(), msg.from.as_ref()) else {
+        return Ok(());
+    };
+
+    let question = without_mention(text, &me);
+    if question.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Ask me something, e.g. @{} what's the price of BTC?",
+                me.username()
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+        return Ok(());
+    }
+
+    let parent = msg.reply_to_message();
+    let thread = parent
+        .and_then(|parent| Some((parent.id, thread_turn(parent, &me)?)))
+        .map(|(parent_id, turn)| thread_service::chain(msg.chat.id, parent_id, turn))
+        .unwrap_or_default();
+    let question = format!("{}: {question}", user.first_name);
+
+    let (reply_id, reply) =
+        send_ai_reply(&bot, user.id, msg.chat.id, &question, Some(msg.id), &thread).await?;
+
+    thread_service::remember(
+        msg.chat.id,
+        msg.id,
+        parent.map(|parent| parent.id),
+        ChatTurn::new(ChatRole::User, &question),
+    );
+    thread_service::remember(
+        msg.chat.id,
+        reply_id,
+        Some(msg.id),
+        ChatTurn::new(ChatRole::Assistant, &reply),
+    );
+    if source == InputSource::Voice {
+        send_voice_reply(&bot, user.id, msg.chat.id, &reply, msg.id).await;
+    }
+    Ok(())
+}
+
+// Spoken answers are a bonus on top of the text one, so failures are only logged
+async fn send_voice_reply(
+    bot: &Bot,
+    user_id: UserId,
+    chat_id: ChatId,
+    text: &str,
+    reply_to: MessageId,
+) {
+    if !settings_service::get(chat_id).voice_replies {
+        return;
+    }
+
+    if let Err(err) = bot.send_chat_action(chat_id, ChatAction::RecordVoice).await {
+        log::warn!("failed to send record voice action to {chat_id}: {err}");
+    }
+
+    let audio = match speech_service::synthesize(user_id, chat_id, text).await {
+        Ok(audio) => audio,
+        Err(err) => {
+            log::warn!("Speech synthesis failed in {}: {}", chat_id, err);
+            return;
+        }
+    };
+
+    let result = bot
+        .send_voice(chat_id, InputFile::memory(audio).file_name("reply.ogg"))
+        .reply_parameters(ReplyParameters::new(reply_to).allow_sending_without_reply())
+        .await;
+    if let Err(err) = result {
+        log::error!("Failed to send a voice reply in {}: {}", chat_id, err);
+    }
+}
+
+// "slash movie Inception" or "/movie Inception." become the command, when the bot has one by that name
+fn spoken_command(transcript: &str, me: &Me) -> Option<String> {
+    let transcript = transcript.trim().trim_end_matches(['.', '!', '?']);
+    let rest = match transcript.strip_prefix('/') {
+        Some(rest) => rest,
+        None => {
+            let (first, rest) = transcript.split_once(char::is_whitespace)?;
+            if !first
+                .trim_end_matches([',', '.'])
+                .eq_ignore_ascii_case("slash")
+            {
+                return None;
+            }
+            rest
+        }
+    };
+
+    let (name, args) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim_start(), ""));
+    let name = name.trim_end_matches([',', '.']).to_lowercase();
+    let command = format!("/{name} {}", args.trim()).trim_end().to_string();
+
+    OtherCommand::parse(&command, me.username())
+        .is_ok()
+        .then_some(command)
+}
+
+fn as_text_message(mut msg: Message, text: String) -> Message {
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.media_kind = MediaKind::Text(MediaText {
+            text,
+            entities: Vec::new(),
+            link_preview_options: None,
+        });
+    }
+    msg
+}
+
+// Voice notes and audio files are transcribed and then handled as if the text had been typed
+pub async fn handle_voice(
+    bot: Bot,
+    msg: Message,
+    me: Me,
+    state: State,
+    storage: Arc<InMemStorage<State>>,
+) -> HandlerResult {
+    // Transcribing costs money, so group chatter is left alone like any other and private voice notes
+    // are only transcribed in AI mode, where both questions and spoken commands can use them
+    if is_group(&msg) && !replies_to_bot(&msg, &me) {
+        return Ok(());
+    }
+    if msg.chat.is_private() && !matches!(state, State::ChatWithAi) {
+        bot.send_message(
+            msg.chat.id,
+            "Open Chat with AI from the menu to talk to the AI by voice. \
+            Start with \"slash\" there to use a command, e.g. \"slash movie Inception\".",
+        )
+        .await?;
+        return Ok(());
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let typing = keep_typing(bot.clone(), msg.chat.id);
+    let result = speech_service::transcribe_message(&bot, &msg, user.id).await;
+    typing.abort();
+
+    let transcript = match result {
+        Ok(Some(transcript)) if !transcript.is_empty() => transcript,
+        Ok(_) => {
+            bot.send_message(
+                msg.chat.id,
+                "I couldn't make out any words in that recording.",
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err @ (SpeechError::TooLong { .. } | SpeechError::TooLarge { .. })) => {
+            bot.send_message(msg.chat.id, err.to_string()).await?;
+            return Ok(());
+        }
+        Err(SpeechError::Ai(AiError::QuotaExceeded(message))) => {
+            bot.send_message(msg.chat.id, message).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            log::error!("Transcription failed in {}: {}", msg.chat.id, err);
+            bot.send_message(
+                msg.chat.id,
+                "Sorry, I couldn't transcribe that recording. Please try again later.",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(msg.chat.id, format!("Heard: {transcript}"))
+        .reply_parameters(ReplyParameters::new(msg.id).allow_sending_without_reply())
+        .await?;
+
+    let command = spoken_command(&transcript, &me);
+    let update = Update {
+        id: UpdateId(0),
+        kind: UpdateKind::Message(as_text_message(msg, command.unwrap_or(transcript))),
+    };
+    match schema()
+        .dispatch(dptree::deps![update, bot, me, storage, InputSource::Voice])
+        .await
+    {
+        ControlFlow::Break(result) => result,
+        ControlFlow::Continue(_) => Ok(()),
+    }
+}
+
+// Photos with a question in the caption go to a vision model, in groups only when meant for the bot
+pub async fn handle_photo(bot: Bot, msg: Message, me: Me) -> HandlerResult {
+    if is_group(&msg) && !addressed_to_bot(msg.clone(), me.clone()) {
+        return Ok(());
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    let question = msg
+        .caption()
+        .map(|caption| without_mention(caption, &me))
+        .unwrap_or_default();
+    if question.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Add your question as the caption, e.g. \"What's in this picture?\"",
+        )
+        .reply_parameters(ReplyParameters::new(msg.id).allow_sending_without_reply())
+        .await?;
+        return Ok(());
+    }
+    let question = if is_group(&msg) {
+        format!("{}: {question}", user.first_name)
+    } else {
+        question
+    };
+
+    let provider = llm_service::provider();
+    let typing = keep_typing(bot.clone(), msg.chat.id);
+    let result = async {
+        let (image, mime) = vision_service::image_from_message(&bot, &msg).await?;
+        let image_url = vision_service::data_url(&image, &mime);
+        Ok::<_, VisionError>(
+            gpt_service::ask_about_image(&provider, user.id, msg.chat.id, &question, image_url)
+                .await?,
+        )
+    }
+    .await;
+    typing.abort();
+
+    let reply = match result {
+        Ok(answer) if !answer.is_empty() => answer,
+        Ok(_) => "The AI had nothing to say about that picture.".to_string(),
+        Err(err @ VisionError::TooLarge { .. }) => err.to_string(),
+        Err(VisionError::Ai(AiError::QuotaExceeded(message) | AiError::Moderated(message))) => {
+            message
+        }
+        Err(err) => {
+            log::error!("Image question failed in {}: {}", msg.chat.id, err);
+            "Sorry, I couldn't look at that picture right now. Please try again later.".to_string()
+        }
+    };
+
+    for part in split_message(&reply, MESSAGE_LIMIT) {
+        bot.send_message(msg.chat.id, part)
+            .reply_parameters(ReplyParameters::new(msg.id).allow_sending_without_reply())
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn set_voice_replies(bot: Bot, msg: Message, value: String) -> HandlerResult {
+    let enabled = match value.trim().to_lowercase().as_str() {
+        "on" | "yes" | "true" => true,
+        "off" | "no" | "false" => false,
+        _ => {
+            let status = if settings_service::get(msg.chat.id).voice_replies {
+                "on"
+            } else {
+                "off"
+            };
+            bot.send_message(
+                msg.chat.id,
+                format!("Voice replies to voice notes are {status}. Use /voice on or /voice off."),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if !can_change_settings(&bot, &msg).await? {
+        bot.send_message(msg.chat.id, "Only group admins can change voice replies.")
+            .await?;
+        return Ok(());
+    }
+
+    settings_service::update(msg.chat.id, |settings| settings.voice_replies = enabled)?;
+    let reply = if enabled {
+        "Voice notes will now be answered with voice too."
+    } else {
+        "Voice notes will be answered with text only."
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+// Anything else said in a group isn't meant for the bot
+async fn ignore_group_chatter() -> HandlerResult {
+    Ok(())
+}
+
+pub async fn reset_conversation(bot: Bot, msg: Message) -> HandlerResult {
+    let reply = match gpt_service::reset(msg.chat.id) {
+        Ok(true) => "Conversation cleared, the AI has forgotten everything said here.",
+        Ok(false) => "There is no AI conversation to clear.",
+        Err(err) => {
+            log::error!("Failed to reset conversation in {}: {}", msg.chat.id, err);
+            "Sorry, I couldn't clear the conversation."
+        }
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+pub async fn leave_ai_chat(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
+    if let Err(err) = gpt_service::reset(msg.chat.id) {
+        log::error!("Failed to reset conversation in {}: {}", msg.chat.id, err);
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        "Left the AI chat and cleared the conversation.",
+    )
+    .await?;
+    dialogue.exit().await?;
     Ok(())
 }
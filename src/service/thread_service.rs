@@ -0,0 +1,50 @@
+use lazy_static::lazy_static;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+use teloxide::types::{ChatId, MessageId};
+
+use crate::models::conversation::{ChatTurn, ThreadMessage};
+
+// Recent AI exchanges kept per group so reply chains can be followed, older ones fall off
+const MAX_THREAD_MESSAGES: usize = 300;
+const MAX_THREAD_DEPTH: usize = 10;
+
+lazy_static! {
+    static ref THREADS: Mutex<HashMap<i64, VecDeque<ThreadMessage>>> = Mutex::new(HashMap::new());
+}
+
+pub fn remember(chat_id: ChatId, id: MessageId, reply_to: Option<MessageId>, turn: ChatTurn) {
+    let mut threads = THREADS.lock().expect("threads lock poisoned");
+    let messages = threads.entry(chat_id.0).or_default();
+
+    messages.push_back(ThreadMessage { id, reply_to, turn });
+    if messages.len() > MAX_THREAD_MESSAGES {
+        messages.pop_front();
+    }
+}
+
+// The replied-to message and the ones it answered, oldest first. Telegram only tells us about the
+// direct parent, so anything further up is found among the remembered messages
+pub fn chain(chat_id: ChatId, parent_id: MessageId, parent: ChatTurn) -> Vec<ChatTurn> {
+    let threads = THREADS.lock().expect("threads lock poisoned");
+    let messages = threads.get(&chat_id.0);
+    let find = |id: MessageId| {
+        messages.and_then(|messages| messages.iter().find(|message| message.id == id))
+    };
+
+    let mut turns = vec![find(parent_id).map_or(parent, |message| message.turn.clone())];
+    let mut next = find(parent_id).and_then(|message| message.reply_to);
+
+    while let Some(message) = next.and_then(find) {
+        if turns.len() >= MAX_THREAD_DEPTH {
+            break;
+        }
+        turns.push(message.turn.clone());
+        next = message.reply_to;
+    }
+
+    turns.reverse();
+    turns
+}
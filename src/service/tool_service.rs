@@ -0,0 +1,248 @@
+use futures::future::BoxFuture;
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+use teloxide::types::ChatId;
+
+use crate::{
+    models::{crypto::Ticker, movie::TrendingMovieResult},
+    service::{
+        conversion_service, crypto_service, movie_service, settings_service, soccer_service,
+    },
+};
+
+type ToolResult = Result<Value, Box<dyn std::error::Error + Send + Sync>>;
+
+// A bot feature the model may call, parameters are a JSON schema for the arguments
+pub struct Tool {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub parameters: Value,
+    handler: fn(ChatId, Value) -> BoxFuture<'static, ToolResult>,
+}
+
+const MAX_MATCHES: usize = 30;
+const MAX_MOVIES: usize = 5;
+const MOVERS_COUNT: usize = 5;
+
+lazy_static! {
+    static ref TOOLS: Vec<Tool> = vec![
+        Tool {
+            name: "get_crypto_price",
+            description: "Current price and 24h statistics of a crypto trading pair on Binance",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "symbol": {"type": "string", "description": "Pair or coin, e.g. BTCUSDT or ETH"}
+                },
+                "required": ["symbol"]
+            }),
+            handler: |_, args| Box::pin(crypto_price(args)),
+        },
+        Tool {
+            name: "get_crypto_movers",
+            description:
+                "Biggest 24h gainers, losers and volume leaders among USDT pairs on Binance",
+            parameters: json!({"type": "object", "properties": {}}),
+            handler: |_, _| Box::pin(crypto_movers()),
+        },
+        Tool {
+            name: "convert_currency",
+            description: "Convert an amount between crypto and fiat assets using Binance rates",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "amount": {"type": "number"},
+                    "from": {"type": "string", "description": "Asset to convert from, e.g. BTC"},
+                    "to": {"type": "string", "description": "Asset to convert to, e.g. EUR"}
+                },
+                "required": ["amount", "from", "to"]
+            }),
+            handler: |_, args| Box::pin(convert(args)),
+        },
+        Tool {
+            name: "get_today_football_matches",
+            description: "Football matches scheduled today with their current scores",
+            parameters: json!({"type": "object", "properties": {}}),
+            handler: |_, _| Box::pin(football_matches()),
+        },
+        Tool {
+            name: "search_movies",
+            description: "Search movies by title, returns release dates, ratings and overviews",
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "Movie title or part of it"}
+                },
+                "required": ["query"]
+            }),
+            handler: |chat_id, args| Box::pin(search_movies(chat_id, args)),
+        },
+        Tool {
+            name: "get_trending_movies",
+            description: "Movies trending today, returns release dates, ratings and overviews",
+            parameters: json!({"type": "object", "properties": {}}),
+            handler: |chat_id, _| Box::pin(trending_movies(chat_id)),
+        },
+    ];
+}
+
+fn string_arg(
+    args: &Value,
+    name: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    args.get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| format!("missing argument {name}").into())
+}
+
+async fn crypto_price(args: Value) -> ToolResult {
+    let symbol = crypto_service::normalize_symbol(&string_arg(&args, "symbol")?)?;
+    let ticker = crypto_service::ticker(&symbol).await?;
+    Ok(serde_json::to_value(ticker)?)
+}
+
+async fn crypto_movers() -> ToolResult {
+    let (gainers, losers, volume) = crypto_service::top_movers(MOVERS_COUNT).await?;
+    let summarize = |tickers: Vec<Ticker>| -> Vec<Value> {
+        tickers
+            .into_iter()
+            .map(|ticker| {
+                json!({
+                    "symbol": ticker.symbol,
+                    "price": ticker.last_price,
+                    "change_percent": ticker.price_change_percent,
+                    "quote_volume": ticker.quote_volume,
+                })
+            })
+            .collect()
+    };
+
+    Ok(json!({
+        "gainers": summarize(gainers),
+        "losers": summarize(losers),
+        "volume": summarize(volume),
+    }))
+}
+
+async fn convert(args: Value) -> ToolResult {
+    let amount = args
+        .get("amount")
+        .and_then(Value::as_f64)
+        .ok_or("missing argument amount")?;
+    let text = format!(
+        "{amount} {} {}",
+        string_arg(&args, "from")?,
+        string_arg(&args, "to")?
+    );
+    let conversion = conversion_service::convert(&text).await?;
+
+    Ok(json!({
+        "amount": conversion.amount.to_string(),
+        "from": conversion.from,
+        "to": conversion.to,
+        "rate": conversion.rate.to_string(),
+        "result": conversion.result.to_string(),
+        "path": conversion.path,
+    }))
+}
+
+async fn football_matches() -> ToolResult {
+    let events = soccer_service::fetch_today_events().await?;
+    let matches: Vec<Value> = events
+        .events
+        .iter()
+        .take(MAX_MATCHES)
+        .map(|event| {
+            json!({
+                "home": event.home_team.name,
+                "away": event.away_team.name,
+                "home_score": event.home_score.as_ref().and_then(|score| score.current),
+                "away_score": event.away_score.as_ref().and_then(|score| score.current),
+            })
+        })
+        .collect();
+
+    Ok(json!({ "total": events.events.len(), "matches": matches }))
+}
+
+fn summarize_movies(movies: &[TrendingMovieResult]) -> Value {
+    let movies: Vec<Value> = movies
+        .iter()
+        .take(MAX_MOVIES)
+        .map(|movie| {
+            json!({
+                "title": movie_service::display_title(movie),
+                "release_date": movie.release_date,
+                "rating": movie.vote_average,
+                "overview": movie.overview,
+            })
+        })
+        .collect();
+
+    json!({ "movies": movies })
+}
+
+// Movie tools respect the chat's adult and language settings
+async fn search_movies(chat_id: ChatId, args: Value) -> ToolResult {
+    let query = string_arg(&args, "query")?;
+    let settings = settings_service::get(chat_id);
+    let language = settings.language_or(None);
+
+    let movies = movie_service::search_movie_results(&query, &settings, &language).await?;
+    Ok(summarize_movies(&movies))
+}
+
+async fn trending_movies(chat_id: ChatId) -> ToolResult {
+    let settings = settings_service::get(chat_id);
+    let language = settings.language_or(None);
+
+    let movies = movie_service::trending_movie_results(&settings, &language).await?;
+    Ok(summarize_movies(&movies))
+}
+
+// Tool definitions in the format of the chat completions API
+pub fn definitions() -> Vec<Value> {
+    TOOLS
+        .iter()
+        .map(|tool| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                }
+            })
+        })
+        .collect()
+}
+
+// Runs a tool requested by the model, failures are reported back to it rather than to the user
+pub async fn call(chat_id: ChatId, name: &str, arguments: &str) -> String {
+    let Some(tool) = TOOLS.iter().find(|tool| tool.name == name) else {
+        return json!({ "error": format!("unknown tool {name}") }).to_string();
+    };
+
+    let args = if arguments.trim().is_empty() {
+        Ok(json!({}))
+    } else {
+        serde_json::from_str(arguments)
+    };
+
+    let result = match args {
+        Ok(args) => (tool.handler)(chat_id, args).await,
+        Err(err) => Err(err.into()),
+    };
+
+    match result {
+        Ok(value) => {
+            log::info!("Tool {} called in {}", name, chat_id);
+            value.to_string()
+        }
+        Err(err) => {
+            log::warn!("Tool {} failed in {}: {}", name, chat_id, err);
+            json!({ "error": err.to_string() }).to_string()
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use lazy_static::lazy_static;
+use rust_decimal::Decimal;
+use std::sync::Mutex;
+use teloxide::types::{ChatId, UserId};
+
+use crate::{
+    models::{
+        assets::{AiError, StorageError},
+        llm::TokenUsage,
+        usage::{QuotaLimits, UsageBook, UsageRecord},
+    },
+    service::settings_service,
+    utils::storage,
+};
+
+const USAGE_FILE: &str = "ai_usage.json";
+const TOKENS_PER_PRICE_UNIT: u64 = 1_000_000;
+
+lazy_static! {
+    static ref USAGE: Mutex<UsageBook> = Mutex::new(storage::load(USAGE_FILE));
+}
+
+// USD per million prompt and completion tokens, None for models the bot doesn't know the price of.
+// Speech models count token equivalents, see speech_service
+fn prices(model: &str) -> Option<(Decimal, Decimal)> {
+    let model = model.to_lowercase();
+
+    if model == "mock" {
+        Some((Decimal::ZERO, Decimal::ZERO))
+    } else if model.starts_with("whisper") || model.starts_with("gpt-4o-transcribe") {
+        Some((Decimal::new(10, 0), Decimal::new(10, 0)))
+    } else if model.starts_with("gpt-4o-mini-transcribe") {
+        Some((Decimal::new(5, 0), Decimal::new(5, 0)))
+    } else if model.starts_with("tts-1-hd") {
+        Some((Decimal::new(30, 0), Decimal::new(30, 0)))
+    } else if model.starts_with("tts-1") || model.starts_with("gpt-4o-mini-tts") {
+        Some((Decimal::new(15, 0), Decimal::new(15, 0)))
+    } else if model.starts_with("gpt-4o-mini") {
+        Some((Decimal::new(15, 2), Decimal::new(60, 2)))
+    } else if model.starts_with("gpt-4o") {
+        Some((Decimal::new(250, 2), Decimal::new(1000, 2)))
+    } else if model.starts_with("gpt-4.1-mini") {
+        Some((Decimal::new(40, 2), Decimal::new(160, 2)))
+    } else if model.starts_with("gpt-4.1") {
+        Some((Decimal::new(200, 2), Decimal::new(800, 2)))
+    } else if model.starts_with("gpt-3.5") {
+        Some((Decimal::new(50, 2), Decimal::new(150, 2)))
+    } else {
+        None
+    }
+}
+
+// Chats may only pick models whose usage can be billed
+pub fn is_priced(model: &str) -> bool {
+    prices(model).is_some()
+}
+
+// Chats can't pick unpriced models, so only the operator's LLM_MODEL or VISION_MODEL gets here without
+// a price. Those are counted at zero cost, the token quotas still apply to them
+pub fn estimate_cost(model: &str, usage: TokenUsage) -> Decimal {
+    let Some((prompt, completion)) = prices(model) else {
+        return Decimal::ZERO;
+    };
+    (prompt * Decimal::from(usage.prompt_tokens)
+        + completion * Decimal::from(usage.completion_tokens))
+        / Decimal::from(TOKENS_PER_PRICE_UNIT)
+}
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+// The stored record may be from an earlier day or month, so it is rolled before being read
+fn current(record: Option<&UsageRecord>) -> UsageRecord {
+    let mut record = record.cloned().unwrap_or_default();
+    record.roll(today());
+    record
+}
+
+pub fn user_usage(user_id: UserId) -> UsageRecord {
+    current(
+        USAGE
+            .lock()
+            .expect("usage lock poisoned")
+            .users
+            .get(&user_id.0),
+    )
+}
+
+pub fn chat_usage(chat_id: ChatId) -> UsageRecord {
+    current(
+        USAGE
+            .lock()
+            .expect("usage lock poisoned")
+            .chats
+            .get(&chat_id.0),
+    )
+}
+
+fn next_month(today: NaiveDate) -> String {
+    let (year, month) = if today.month() == 12 {
+        (today.year() + 1, 1)
+    } else {
+        (today.year(), today.month() + 1)
+    };
+
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .map(|date| date.format("%B %-d").to_string())
+        .unwrap_or_else(|| "next month".to_string())
+}
+
+// Refuses a new AI request once the user has spent the chat's daily or monthly allowance
+pub fn check_quota(user_id: UserId, chat_id: ChatId) -> Result<(), AiError> {
+    let limits = settings_service::get(chat_id).quota;
+    let usage = user_usage(user_id);
+
+    if usage.today.tokens() >= limits.daily_tokens {
+        return Err(AiError::QuotaExceeded(format!(
+            "You've used your daily AI allowance ({} of {} tokens). It resets at midnight UTC, see /usage for details.",
+            usage.today.tokens(),
+            limits.daily_tokens
+        )));
+    }
+
+    if usage.this_month.tokens() >= limits.monthly_tokens {
+        return Err(AiError::QuotaExceeded(format!(
+            "You've used your monthly AI allowance ({} of {} tokens). It resets on {}, see /usage for details.",
+            usage.this_month.tokens(),
+            limits.monthly_tokens,
+            next_month(today())
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn record(
+    user_id: UserId,
+    chat_id: ChatId,
+    model: &str,
+    usage: TokenUsage,
+) -> Result<(), StorageError> {
+    let cost = estimate_cost(model, usage);
+    let today = today();
+
+    let mut book = USAGE.lock().expect("usage lock poisoned");
+    book.users.entry(user_id.0).or_default().add(
+        today,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        cost,
+    );
+    book.chats.entry(chat_id.0).or_default().add(
+        today,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        cost,
+    );
+
+    storage::save(USAGE_FILE, &*book)
+}
+
+pub fn set_limits(chat_id: ChatId, limits: QuotaLimits) -> Result<QuotaLimits, StorageError> {
+    let settings = settings_service::update(chat_id, |settings| settings.quota = limits)?;
+    Ok(settings.quota)
+}
+
+fn format_cost(cost: Decimal) -> String {
+    format!("${}", cost.round_dp(4).normalize())
+}
+
+fn percent(used: u64, limit: u64) -> u64 {
+    used * 100 / limit.max(1)
+}
+
+pub fn describe(user_id: UserId, chat_id: ChatId) -> String {
+    let limits = settings_service::get(chat_id).quota;
+    let usage = user_usage(user_id);
+
+    let mut message = String::from("Your AI usage:\n\n");
+    message.push_str(&format!(
+        "Today: {} of {} tokens ({}%), {} requests\n",
+        usage.today.tokens(),
+        limits.daily_tokens,
+        percent(usage.today.tokens(), limits.daily_tokens),
+        usage.today.requests
+    ));
+    message.push_str(&format!(
+        "This month: {} of {} tokens ({}%), {} requests\n",
+        usage.this_month.tokens(),
+        limits.monthly_tokens,
+        percent(usage.this_month.tokens(), limits.monthly_tokens),
+        usage.this_month.requests
+    ));
+    message.push_str(&format!(
+        "Prompt / completion this month: {} / {}\n",
+        usage.this_month.prompt_tokens, usage.this_month.completion_tokens
+    ));
+    message.push_str(&format!(
+        "Estimated cost: {} today, {} this month, {} all time\n",
+        format_cost(usage.today.cost),
+        format_cost(usage.this_month.cost),
+        format_cost(usage.all_time.cost)
+    ));
+
+    if !chat_id.is_user() {
+        let chat = chat_usage(chat_id);
+        message.push_str(&format!(
+            "\nThis chat this month: {} tokens in {} requests, about {}\n",
+            chat.this_month.tokens(),
+            chat.this_month.requests,
+            format_cost(chat.this_month.cost)
+        ));
+    }
+
+    message.push_str("\nDaily limits reset at midnight UTC, monthly ones on the 1st.");
+    message
+}
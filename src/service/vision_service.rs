@@ -0,0 +1,59 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use teloxide::prelude::*;
+
+use crate::{models::assets::VisionError, utils::helpers};
+
+// Larger images cost more tokens without helping the answer much
+const MAX_IMAGE_MB: u32 = 5;
+const MAX_IMAGE_SIDE: u32 = 2048;
+const SUPPORTED_TYPES: [&str; 4] = ["image/jpeg", "image/png", "image/webp", "image/gif"];
+
+pub fn is_image(msg: &Message) -> bool {
+    msg.photo().is_some()
+        || msg
+            .document()
+            .and_then(|document| document.mime_type.as_ref())
+            .is_some_and(|mime| SUPPORTED_TYPES.contains(&mime.essence_str()))
+}
+
+// Telegram keeps each photo in several sizes, the largest one within the limits is used
+pub async fn image_from_message(
+    bot: &Bot,
+    msg: &Message,
+) -> Result<(Vec<u8>, String), VisionError> {
+    let limit = MAX_IMAGE_MB * 1024 * 1024;
+
+    let (file_id, mime) = if let Some(sizes) = msg.photo() {
+        let photo = sizes
+            .iter()
+            .filter(|photo| {
+                photo.file.size <= limit && photo.width.max(photo.height) <= MAX_IMAGE_SIDE
+            })
+            .max_by_key(|photo| photo.width * photo.height)
+            .ok_or(VisionError::TooLarge {
+                limit: MAX_IMAGE_MB,
+            })?;
+        (photo.file.id.clone(), "image/jpeg".to_string())
+    } else {
+        let document = msg.document().ok_or(VisionError::NoImage)?;
+        if document.file.size > limit {
+            return Err(VisionError::TooLarge {
+                limit: MAX_IMAGE_MB,
+            });
+        }
+        let mime = document
+            .mime_type
+            .as_ref()
+            .map_or("image/jpeg".to_string(), |mime| {
+                mime.essence_str().to_string()
+            });
+        (document.file.id.clone(), mime)
+    };
+
+    let image = helpers::download_file::<VisionError>(bot, &file_id).await?;
+    Ok((image, mime))
+}
+
+pub fn data_url(image: &[u8], mime: &str) -> String {
+    format!("data:{mime};base64,{}", STANDARD.encode(image))
+}
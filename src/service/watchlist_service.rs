@@ -0,0 +1,126 @@
+use lazy_static::lazy_static;
+use std::{collections::HashMap, sync::Mutex};
+use teloxide::types::UserId;
+
+use crate::{
+    models::assets::{CryptoError, StorageError},
+    service::crypto_service,
+    utils::storage,
+};
+
+const WATCHLIST_FILE: &str = "watchlists.json";
+pub const MAX_WATCHLIST_SIZE: usize = 20;
+
+lazy_static! {
+    static ref WATCHLISTS: Mutex<HashMap<u64, Vec<String>>> =
+        Mutex::new(storage::load(WATCHLIST_FILE));
+}
+
+pub fn get(user_id: UserId) -> Vec<String> {
+    WATCHLISTS
+        .lock()
+        .expect("watchlist lock poisoned")
+        .get(&user_id.0)
+        .cloned()
+        .unwrap_or_default()
+}
+
+// Every pair on any watchlist
+pub fn watched_symbols() -> Vec<String> {
+    let watchlists = WATCHLISTS.lock().expect("watchlist lock poisoned");
+    let mut symbols: Vec<String> = watchlists.values().flatten().cloned().collect();
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+// Adds the pairs Binance knows about and returns the ones it rejected
+pub async fn add(
+    user_id: UserId,
+    inputs: &[&str],
+) -> Result<(Vec<String>, Vec<String>), CryptoError> {
+    let mut candidates = Vec::new();
+    let mut rejected = Vec::new();
+
+    for input in inputs {
+        match crypto_service::normalize_symbol(input) {
+            Ok(symbol) => candidates.push(symbol),
+            Err(_) => rejected.push(input.to_string()),
+        }
+    }
+
+    let known: Vec<String> = crypto_service::tickers(&candidates)
+        .await?
+        .into_iter()
+        .map(|ticker| ticker.symbol)
+        .collect();
+    rejected.extend(
+        candidates
+            .iter()
+            .filter(|symbol| !known.contains(symbol))
+            .cloned(),
+    );
+
+    let mut watchlists = WATCHLISTS.lock().expect("watchlist lock poisoned");
+    let watchlist = watchlists.entry(user_id.0).or_default();
+    let mut added = Vec::new();
+
+    for symbol in known {
+        if watchlist.len() >= MAX_WATCHLIST_SIZE {
+            rejected.push(symbol);
+        } else if !watchlist.contains(&symbol) {
+            watchlist.push(symbol.clone());
+            added.push(symbol);
+        }
+    }
+
+    storage::save(WATCHLIST_FILE, &*watchlists)?;
+
+    Ok((added, rejected))
+}
+
+pub fn remove(user_id: UserId, inputs: &[&str]) -> Result<Vec<String>, StorageError> {
+    let symbols: Vec<String> = inputs
+        .iter()
+        .filter_map(|input| crypto_service::normalize_symbol(input).ok())
+        .collect();
+
+    let mut watchlists = WATCHLISTS.lock().expect("watchlist lock poisoned");
+    let watchlist = watchlists.entry(user_id.0).or_default();
+    let before = watchlist.clone();
+    watchlist.retain(|symbol| !symbols.contains(symbol));
+    let removed = before
+        .into_iter()
+        .filter(|symbol| !watchlist.contains(symbol))
+        .collect();
+
+    storage::save(WATCHLIST_FILE, &*watchlists)?;
+
+    Ok(removed)
+}
+
+// Watchlist rendered as one html <pre> table
+pub async fn table(user_id: UserId) -> Result<String, CryptoError> {
+    let symbols = get(user_id);
+
+    if symbols.is_empty() {
+        return Ok("Your watchlist is empty. Add pairs with /watch BTC ETH SOL".to_string());
+    }
+
+    let tickers = crypto_service::tickers(&symbols).await?;
+    Ok(format!(
+        "<b>Watchlist</b>\n<pre>{}</pre>",
+        crypto_service::format_ticker_table(&tickers, false)
+    ))
+}
+
+pub async fn top_movers_table() -> Result<String, CryptoError> {
+    let (gainers, losers, volume) = crypto_service::top_movers(5).await?;
+
+    Ok(format!(
+        "<b>Top gainers (24h)</b>\n<pre>{}</pre>\n<b>Top losers (24h)</b>\n<pre>{}</pre>\n<b>Highest volume (USDT)</b>\n<pre>{}</pre>",
+        crypto_service::format_ticker_table(&gainers, false),
+        crypto_service::format_ticker_table(&losers, false),
+        crypto_service::format_ticker_table(&volume, true)
+    ))
+}
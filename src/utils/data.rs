@@ -1,21 +1,48 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
+use crate::models::{moderation::ModerationRule, persona::Persona};
+
 lazy_static! {
     pub static ref PROMPT_DATA: HashMap<String, Vec<&'static str>> = {
         let mut m = HashMap::new();
         m.insert(
             "Get Live Scores".to_string(),
-            vec!["today event", "Current Live match", "transfer window", "Odds for all event scheduled"],
+            vec![
+                "today event",
+                "Current Live match",
+                "transfer window",
+                "Odds for all event scheduled",
+            ],
         );
         m.insert(
             "Get latest crypto charts".to_string(),
-            vec!["red", "green", "blue"],
+            vec![
+                "Price lookup",
+                "Price chart",
+                "Price alerts",
+                "Watchlist",
+                "Top movers",
+                "Convert",
+                "Order book",
+                "What if",
+            ],
         );
         m.insert(
             "top trending movies".to_string(),
-            vec!["Top trending Movie", "Popular Movie", "Movies in Theatres", "Upcoming Movie"],
+            vec![
+                "Top trending Movie",
+                "Popular Movie",
+                "Movies in Theatres",
+                "Upcoming Movie",
+            ],
         );
         m
     };
+    pub static ref PERSONA_PRESETS: Vec<Persona> =
+        serde_json::from_str(include_str!("../../assets/data/personas.json"))
+            .expect("invalid persona presets");
+    pub static ref MODERATION_RULES: Vec<ModerationRule> =
+        serde_json::from_str(include_str!("../../assets/data/moderation_rules.json"))
+            .expect("invalid moderation rules");
 }
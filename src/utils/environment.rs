@@ -1,6 +1,11 @@
 use std::{cell::OnceCell, env, sync::OnceLock};
 
-use teloxide::{payloads::SendMessageSetters, prelude::Requester, types::MessageId, Bot};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{MessageId, UserId},
+    Bot,
+};
 
 use crate::models::assets::{LogError, Vars};
 pub static VARS: OnceLock<Vars> = OnceLock::new();
@@ -10,6 +15,15 @@ pub fn init_vars() -> Result<(), &'static str> {
         .map_err(|_| "Failed to initialize VARS")
 }
 
+// Bot operators listed in BOT_ADMINS, comma separated user ids
+pub fn is_bot_admin(user_id: UserId) -> bool {
+    env::var("BOT_ADMINS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.trim().parse::<u64>().ok())
+        .any(|id| id == user_id.0)
+}
+
 // Send message to service chat without notification
 pub async fn log(text: &str) -> Result<(), LogError> {
     let vars = VARS.get().ok_or(LogError::VarsNotInitialized)?;
@@ -1,3 +1,7 @@
+use std::time::Duration;
+use teloxide::{net::Download, prelude::*, types::ChatAction, DownloadError, RequestError};
+use tokio::task::JoinHandle;
+
 use crate::models::soccer::TodayApiResponse;
 
 fn format_events(events: TodayApiResponse) -> String {
@@ -14,3 +18,100 @@ fn format_events(events: TodayApiResponse) -> String {
     }
     message
 }
+
+// Private chats manage their own settings, in groups only admins may change them
+pub async fn can_change_settings(bot: &Bot, msg: &Message) -> Result<bool, RequestError> {
+    if msg.chat.is_private() {
+        return Ok(true);
+    }
+
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+
+    let member = bot.get_chat_member(msg.chat.id, user.id).await?;
+    Ok(member.is_privileged())
+}
+
+// Telegram shows a chat action for about five seconds, so it is repeated until the task is aborted
+pub fn keep_typing(bot: Bot, chat_id: ChatId) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = bot.send_chat_action(chat_id, ChatAction::Typing).await {
+                log::warn!("failed to send typing action to {chat_id}: {err}");
+            }
+            tokio::time::sleep(Duration::from_secs(4)).await;
+        }
+    })
+}
+
+// Splits text into pieces under Telegram's message limit, preferring line breaks
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+
+    for line in text.split_inclusive('\n') {
+        let mut line = line;
+        while current.chars().count() + line.chars().count() > limit {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            let split = line
+                .char_indices()
+                .nth(limit)
+                .map_or(line.len(), |(index, _)| index);
+            parts.push(line[..split].to_string());
+            line = &line[split..];
+        }
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+// Fetches a file users sent to the bot, Telegram lets bots download up to 20 MB
+pub async fn download_file<E>(bot: &Bot, file_id: &str) -> Result<Vec<u8>, E>
+where
+    E: From<RequestError> + From<DownloadError>,
+{
+    let file = bot.get_file(file_id).await?;
+    let mut content = Vec::with_capacity(file.meta.size as usize);
+    bot.download_file(&file.path, &mut content).await?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_stays_whole() {
+        assert_eq!(split_message("hello\nworld", 20), ["hello\nworld"]);
+        assert!(split_message("", 20).is_empty());
+    }
+
+    #[test]
+    fn splits_on_line_breaks() {
+        assert_eq!(
+            split_message("first line\nsecond line\n", 12),
+            ["first line\n", "second line\n"]
+        );
+    }
+
+    #[test]
+    fn cuts_lines_longer_than_the_limit() {
+        assert_eq!(split_message("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        let parts = split_message("ééééé", 2);
+
+        assert_eq!(parts, ["éé", "éé", "é"]);
+        assert!(parts.iter().all(|part| part.chars().count() <= 2));
+    }
+}
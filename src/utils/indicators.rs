@@ -0,0 +1,276 @@
+// Indicator series are aligned with their input, None until enough values are available
+
+type ThreeSeries = (Vec<Option<f64>>, Vec<Option<f64>>, Vec<Option<f64>>);
+
+pub fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    if period == 0 {
+        return vec![None; values.len()];
+    }
+
+    let mut result = Vec::with_capacity(values.len());
+    let mut sum = 0.0;
+
+    for (index, value) in values.iter().enumerate() {
+        sum += value;
+        if index >= period {
+            sum -= values[index - period];
+        }
+        result.push((index + 1 >= period).then(|| sum / period as f64));
+    }
+
+    result
+}
+
+// Seeded with the simple average of the first period
+pub fn ema(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    if period == 0 || values.len() < period {
+        return vec![None; values.len()];
+    }
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut result = vec![None; period - 1];
+    let mut current = values[..period].iter().sum::<f64>() / period as f64;
+    result.push(Some(current));
+
+    for value in &values[period..] {
+        current = alpha * value + (1.0 - alpha) * current;
+        result.push(Some(current));
+    }
+
+    result
+}
+
+// Wilder's smoothing, the usual 14 period RSI
+pub fn rsi(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    if period == 0 || values.len() <= period {
+        return vec![None; values.len()];
+    }
+
+    let changes: Vec<f64> = values.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let mut gain = changes[..period]
+        .iter()
+        .filter(|change| **change > 0.0)
+        .sum::<f64>()
+        / period as f64;
+    let mut loss = -changes[..period]
+        .iter()
+        .filter(|change| **change < 0.0)
+        .sum::<f64>()
+        / period as f64;
+
+    // Flat prices have neither gains nor losses, which is neutral rather than overbought
+    let value = |gain: f64, loss: f64| {
+        if gain == 0.0 && loss == 0.0 {
+            50.0
+        } else if loss == 0.0 {
+            100.0
+        } else {
+            100.0 - 100.0 / (1.0 + gain / loss)
+        }
+    };
+
+    let mut result = vec![None; period];
+    result.push(Some(value(gain, loss)));
+
+    for change in &changes[period..] {
+        gain = (gain * (period - 1) as f64 + change.max(0.0)) / period as f64;
+        loss = (loss * (period - 1) as f64 + (-change).max(0.0)) / period as f64;
+        result.push(Some(value(gain, loss)));
+    }
+
+    result
+}
+
+// MACD line, signal line and histogram
+pub fn macd(values: &[f64], fast: usize, slow: usize, signal: usize) -> ThreeSeries {
+    let fast = ema(values, fast);
+    let slow = ema(values, slow);
+
+    let line: Vec<Option<f64>> = fast
+        .iter()
+        .zip(&slow)
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+
+    // The signal line is an EMA over the defined part of the MACD line
+    let start = line.iter().position(Option::is_some).unwrap_or(line.len());
+    let defined: Vec<f64> = line[start..].iter().flatten().copied().collect();
+    let mut signal_line = vec![None; start];
+    signal_line.extend(ema(&defined, signal));
+
+    let histogram = line
+        .iter()
+        .zip(&signal_line)
+        .map(|(line, signal)| Some((*line)? - (*signal)?))
+        .collect();
+
+    (line, signal_line, histogram)
+}
+
+// Upper band, middle band and lower band
+pub fn bollinger(values: &[f64], period: usize, deviations: f64) -> ThreeSeries {
+    let middle = sma(values, period);
+    let mut upper = Vec::with_capacity(values.len());
+    let mut lower = Vec::with_capacity(values.len());
+
+    for (index, mean) in middle.iter().enumerate() {
+        let band = mean.map(|mean| {
+            let window = &values[index + 1 - period..=index];
+            let variance = window
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / period as f64;
+            (mean, variance.sqrt() * deviations)
+        });
+        upper.push(band.map(|(mean, width)| mean + width));
+        lower.push(band.map(|(mean, width)| mean - width));
+    }
+
+    (upper, middle, lower)
+}
+
+// Index of the most recent candle where `fast` crossed `slow`, with true for an upward cross
+pub fn last_cross(fast: &[Option<f64>], slow: &[Option<f64>]) -> Option<(usize, bool)> {
+    let spread: Vec<Option<f64>> = fast
+        .iter()
+        .zip(slow)
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+
+    (1..spread.len())
+        .rev()
+        .find_map(|index| match (spread[index - 1], spread[index]) {
+            (Some(before), Some(after)) if before <= 0.0 && after > 0.0 => Some((index, true)),
+            (Some(before), Some(after)) if before >= 0.0 && after < 0.0 => Some((index, false)),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: Option<f64>, expected: f64) -> bool {
+        actual.is_some_and(|actual| (actual - expected).abs() < 1e-9)
+    }
+
+    #[test]
+    fn sma_warms_up_then_averages() {
+        let result = sma(&[1.0, 2.0, 3.0, 4.0, 5.0], 3);
+
+        assert_eq!(result[..2], [None, None]);
+        assert!(close(result[2], 2.0));
+        assert!(close(result[3], 3.0));
+        assert!(close(result[4], 4.0));
+    }
+
+    #[test]
+    fn zero_period_and_short_series_have_no_values() {
+        assert_eq!(sma(&[1.0, 2.0], 0), vec![None, None]);
+        assert_eq!(ema(&[1.0, 2.0], 3), vec![None, None]);
+        assert_eq!(rsi(&[1.0, 2.0, 3.0], 3), vec![None, None, None]);
+        assert!(sma(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn ema_is_seeded_with_the_sma() {
+        let result = ema(&[2.0, 4.0, 6.0, 8.0], 3);
+
+        assert_eq!(result[..2], [None, None]);
+        assert!(close(result[2], 4.0));
+        // alpha = 0.5: 0.5 * 8 + 0.5 * 4
+        assert!(close(result[3], 6.0));
+    }
+
+    #[test]
+    fn rsi_of_rising_prices_is_100() {
+        let values: Vec<f64> = (1..=20).map(f64::from).collect();
+        let result = rsi(&values, 14);
+
+        assert_eq!(result.len(), values.len());
+        assert!(result[..14].iter().all(Option::is_none));
+        assert!(close(result[14], 100.0));
+        assert!(close(result[19], 100.0));
+    }
+
+    #[test]
+    fn rsi_of_falling_prices_is_0() {
+        let values: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+
+        assert!(close(rsi(&values, 14)[19], 0.0));
+    }
+
+    #[test]
+    fn rsi_of_flat_prices_is_neutral() {
+        let result = rsi(&[5.0; 20], 14);
+
+        assert!(result[14..].iter().all(|value| close(*value, 50.0)));
+    }
+
+    #[test]
+    fn rsi_balances_equal_gains_and_losses() {
+        let values = [1.0, 2.0, 1.0, 2.0, 1.0];
+
+        assert!(close(rsi(&values, 4)[4], 50.0));
+    }
+
+    #[test]
+    fn macd_warms_up_over_the_slow_and_signal_periods() {
+        let values: Vec<f64> = (1..=40).map(f64::from).collect();
+        let (line, signal, histogram) = macd(&values, 12, 26, 9);
+
+        assert_eq!(line.iter().position(Option::is_some), Some(25));
+        assert_eq!(signal.iter().position(Option::is_some), Some(33));
+        assert_eq!(histogram.iter().position(Option::is_some), Some(33));
+        // A steady rise keeps the fast average above the slow one
+        assert!(line[39].is_some_and(|value| value > 0.0));
+    }
+
+    #[test]
+    fn macd_of_a_short_series_is_empty() {
+        let (line, signal, histogram) = macd(&[1.0, 2.0, 3.0], 12, 26, 9);
+
+        assert!(line
+            .iter()
+            .chain(&signal)
+            .chain(&histogram)
+            .all(Option::is_none));
+        assert_eq!(signal.len(), 3);
+    }
+
+    #[test]
+    fn bollinger_bands_are_standard_deviations_around_the_sma() {
+        let (upper, middle, lower) = bollinger(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0], 8, 2.0);
+
+        assert!(upper[..7].iter().all(Option::is_none));
+        assert!(close(middle[7], 5.0));
+        assert!(close(upper[7], 9.0));
+        assert!(close(lower[7], 1.0));
+    }
+
+    #[test]
+    fn bollinger_of_flat_prices_collapses() {
+        let (upper, middle, lower) = bollinger(&[3.0; 5], 5, 2.0);
+
+        assert!(close(upper[4], 3.0) && close(middle[4], 3.0) && close(lower[4], 3.0));
+    }
+
+    #[test]
+    fn last_cross_finds_the_most_recent_cross() {
+        let fast = [Some(1.0), Some(3.0), Some(3.0), Some(1.0)];
+        let slow = [Some(2.0), Some(2.0), Some(2.0), Some(2.0)];
+
+        assert_eq!(last_cross(&fast, &slow), Some((3, false)));
+        assert_eq!(last_cross(&fast[..3], &slow[..3]), Some((1, true)));
+    }
+
+    #[test]
+    fn last_cross_ignores_undefined_values() {
+        let fast = [None, Some(3.0), Some(4.0)];
+        let slow = [Some(2.0), Some(2.0), Some(2.0)];
+
+        assert_eq!(last_cross(&fast, &slow), None);
+        assert_eq!(last_cross(&[], &[]), None);
+    }
+}
@@ -1,5 +1,8 @@
+pub mod custom_error_handler;
 pub mod data;
 pub mod environment;
-pub mod custom_error_handler;
 pub mod helpers;
-pub mod logger;
\ No newline at end of file
+pub mod indicators;
+pub mod logger;
+pub mod storage;
+pub mod tokens;
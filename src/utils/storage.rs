@@ -0,0 +1,116 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::models::assets::StorageError;
+
+const DEFAULT_STORE_DIR: &str = "store";
+
+// Location of the json files the bot keeps between restarts
+fn store_path(file: &str) -> PathBuf {
+    let dir = env::var("STORE_DIR").unwrap_or_else(|_| DEFAULT_STORE_DIR.to_string());
+    PathBuf::from(dir).join(file)
+}
+
+// The path with a suffix appended, e.g. alerts.json.tmp
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Load a json file from the store, falling back to the default value when it is missing or unreadable
+pub fn load<T: DeserializeOwned + Default>(file: &str) -> T {
+    load_from(&store_path(file))
+}
+
+// An unparsable file is moved aside as *.corrupt first, so the next save can't overwrite what it held
+fn load_from<T: DeserializeOwned + Default>(path: &Path) -> T {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(err) => {
+            log::error!("failed to read {}: {}", path.display(), err);
+            return T::default();
+        }
+    };
+
+    serde_json::from_str(&content).unwrap_or_else(|err| {
+        let corrupt = sibling(path, ".corrupt");
+        log::error!(
+            "failed to parse {}, moving it to {}: {}",
+            path.display(),
+            corrupt.display(),
+            err
+        );
+        if let Err(err) = fs::rename(path, &corrupt) {
+            log::error!("failed to move {} aside: {}", path.display(), err);
+        }
+        T::default()
+    })
+}
+
+pub fn save<T: Serialize>(file: &str, value: &T) -> Result<(), StorageError> {
+    save_to(&store_path(file), value)
+}
+
+// Written to a temporary file first and renamed over the old one, so a crash never leaves half a file
+fn save_to<T: Serialize>(path: &Path, value: &T) -> Result<(), StorageError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let content = serde_json::to_string_pretty(value)?;
+    let temporary = sibling(path, ".tmp");
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("crunchy_bot_storage_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn saves_and_loads_values() {
+        let path = scratch_dir("round_trip").join("values.json");
+        let values = HashMap::from([("btc".to_string(), 1u64)]);
+
+        save_to(&path, &values).unwrap();
+
+        assert_eq!(load_from::<HashMap<String, u64>>(&path), values);
+        assert!(!sibling(&path, ".tmp").exists());
+    }
+
+    #[test]
+    fn missing_files_load_the_default() {
+        let path = scratch_dir("missing").join("values.json");
+
+        assert!(load_from::<HashMap<String, u64>>(&path).is_empty());
+    }
+
+    #[test]
+    fn corrupt_files_are_moved_aside() {
+        let path = scratch_dir("corrupt").join("values.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        assert!(load_from::<HashMap<String, u64>>(&path).is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(sibling(&path, ".corrupt")).unwrap(),
+            "{ not json"
+        );
+    }
+}
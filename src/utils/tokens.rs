@@ -0,0 +1,34 @@
+use crate::models::{conversation::ChatTurn, llm::WireMessage};
+
+// Every chat message costs a few tokens for its role and separators
+const MESSAGE_OVERHEAD: usize = 4;
+// What a typical photo costs a vision model at automatic detail
+const IMAGE_TOKENS: usize = 800;
+
+// Rough GPT token count, about four characters or three quarters of a word per token
+pub fn estimate_tokens(text: &str) -> usize {
+    let by_chars = text.chars().count().div_ceil(4);
+    let by_words = (text.split_whitespace().count() * 4).div_ceil(3);
+    by_chars.max(by_words)
+}
+
+pub fn estimate_turn(turn: &ChatTurn) -> usize {
+    estimate_tokens(&turn.content) + MESSAGE_OVERHEAD
+}
+
+pub fn estimate_turns(turns: &[ChatTurn]) -> usize {
+    turns.iter().map(estimate_turn).sum()
+}
+
+pub fn estimate_messages(messages: &[WireMessage]) -> usize {
+    messages
+        .iter()
+        .map(|message| {
+            let images = message
+                .content
+                .as_ref()
+                .map_or(0, |content| content.images());
+            estimate_tokens(&message.text()) + images * IMAGE_TOKENS + MESSAGE_OVERHEAD
+        })
+        .sum()
+}